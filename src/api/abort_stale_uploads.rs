@@ -0,0 +1,275 @@
+//! This module contains an api to abort the multipart uploads which are in progress for too long.
+//!
+//! Uploads orphaned by a crashed process keep their parts on ucloud.cn until they are aborted.
+//! Every in-progress upload under the prefix older than the given age is aborted, or only
+//! reported in dry-run mode.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_util::{StreamExt, stream};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    api::{
+        ApiOperation, ApiRequest, ListMultipartUploadsRequestBuilder, MultipartAbortRequestBuilder,
+        object::{AbortStaleUploadsReport, MultipartUploadSummary},
+    },
+    constant::{DEFAULT_CONCURRENCY, MULTIPART_SIZE},
+    define_api_request, define_operation_struct,
+    error::UFileError,
+};
+
+define_operation_struct!(AbortStaleUploadsOperation);
+
+define_api_request!(
+    AbortStaleUploadsRequest,
+    AbortStaleUploadsOperationBuilder,
+    AbortStaleUploadsReport,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Uploads started longer than this ago are stale.
+        pub older_than: Duration,
+
+        /// Optional: Only abort uploads of keys beginning with the prefix.
+        #[builder(setter(into, strip_option), default)]
+        pub prefix: Option<String>,
+
+        /// Optional: Only report the stale uploads without aborting them.
+        /// Default: false
+        #[builder(default)]
+        pub dry_run: bool,
+
+        /// Optional: Number of uploads aborted concurrently.
+        ///
+        /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
+        #[builder(setter(into, strip_option), default)]
+        pub concurrency: Option<usize>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl AbortStaleUploadsRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+impl AbortStaleUploadsOperation {
+    /// List every in-progress upload under the prefix, following the next marker.
+    async fn list_uploads(
+        &self,
+        bucket_name: &str,
+        prefix: Option<&str>,
+        security_token: Option<&str>,
+    ) -> Result<Vec<MultipartUploadSummary>, UFileError> {
+        let mut uploads = vec![];
+        let mut marker: Option<String> = None;
+        loop {
+            let page = ListMultipartUploadsRequestBuilder::default()
+                .object_config(self.object_config.clone())
+                .client(self.client.clone())
+                .bucket_name(bucket_name)
+                .prefix(prefix.map(ToString::to_string))
+                .marker(marker.clone())
+                .security_token(security_token.map(ToString::to_string))
+                .build()?
+                .request()
+                .await?;
+            uploads.extend(page.uploads);
+            match page.next_marker {
+                Some(next_marker)
+                    if !next_marker.is_empty() && Some(&next_marker) != marker.as_ref() =>
+                {
+                    marker = Some(next_marker)
+                }
+                _ => return Ok(uploads),
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for AbortStaleUploadsOperation {
+    type Request = AbortStaleUploadsRequest;
+    type Response = AbortStaleUploadsReport;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let AbortStaleUploadsRequest {
+            bucket_name,
+            older_than,
+            prefix,
+            dry_run,
+            concurrency,
+            security_token,
+            ..
+        } = req;
+        let deadline = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| UFileError::InvalidRequest(e.to_string()))?
+            .saturating_sub(older_than)
+            .as_secs();
+        let stale = self
+            .list_uploads(&bucket_name, prefix.as_deref(), security_token.as_deref())
+            .await?
+            .into_iter()
+            // Uploads without a start time can not be judged, keep them.
+            .filter(|upload| {
+                upload
+                    .start_time
+                    .is_some_and(|start_time| start_time < deadline)
+            })
+            .collect::<Vec<_>>();
+        tracing::info!(
+            "Found {} stale uploads of bucket: {} under prefix: {:?}",
+            stale.len(),
+            bucket_name,
+            prefix
+        );
+        if dry_run {
+            return Ok(AbortStaleUploadsReport {
+                dry_run,
+                aborted: stale,
+                failed: vec![],
+            });
+        }
+
+        let results = stream::iter(stale)
+            .map(|upload| {
+                let security_token = security_token.clone();
+                async move {
+                    // Aborting ignores the block size, the mime type is only signed.
+                    let state = upload.to_init_state(
+                        Some("application/octet-stream".to_string()),
+                        MULTIPART_SIZE as u64,
+                    );
+                    let result = match MultipartAbortRequestBuilder::default()
+                        .object_config(self.object_config.clone())
+                        .client(self.client.clone())
+                        .state(state)
+                        .security_token(security_token)
+                        .build()
+                    {
+                        Ok(request) => request.request().await,
+                        Err(e) => Err(e),
+                    };
+                    (upload, result)
+                }
+            })
+            .buffer_unordered(concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1))
+            .collect::<Vec<_>>()
+            .await;
+        let mut report = AbortStaleUploadsReport::default();
+        for (upload, result) in results {
+            match result {
+                Ok(()) => report.aborted.push(upload),
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to abort stale upload {} of {}: {:?}",
+                        upload.upload_id,
+                        upload.key_name,
+                        e
+                    );
+                    report.failed.push((upload, e));
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[test]
+fn test_abort_stale_uploads() {
+    use crate::{
+        client::HttpClient,
+        util::test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        // Two uploads are a day old, the upload of `fresh` has just started.
+        let (object_config, log) = serve(move |_, received| {
+            if received.method == "GET" {
+                let body = format!(
+                    r#"{{"RetCode":0,"NextMarker":"","DataSet":[
+                        {{"UploadId":"up1","FileName":"stale","StartTime":{}}},
+                        {{"UploadId":"up2","FileName":"locked","StartTime":{}}},
+                        {{"UploadId":"up3","FileName":"fresh","StartTime":{now}}}]}}"#,
+                    now - 86400,
+                    now - 86400
+                );
+                (200, vec![], body)
+            } else if received.uri.starts_with("/locked") {
+                let body = r#"{"RetCode":-148653,"ErrMsg":"no permission"}"#;
+                (403, vec![], body.to_string())
+            } else {
+                (204, vec![], String::new())
+            }
+        })
+        .await;
+        let abort = |dry_run: bool| {
+            AbortStaleUploadsRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .older_than(Duration::from_secs(3600))
+                .dry_run(dry_run)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let upload_ids = |uploads: &[MultipartUploadSummary]| {
+            let mut upload_ids = uploads
+                .iter()
+                .map(|upload| upload.upload_id.clone())
+                .collect::<Vec<_>>();
+            upload_ids.sort();
+            upload_ids
+        };
+        let aborted_uris = || {
+            let mut uris = std::mem::take(&mut *log.lock().unwrap())
+                .into_iter()
+                .filter(|received| received.method == "DELETE")
+                .map(|received| received.uri)
+                .collect::<Vec<_>>();
+            uris.sort();
+            uris
+        };
+
+        // Dry run only reports the stale uploads.
+        let report = abort(true).await.unwrap();
+        assert!(report.dry_run);
+        assert_eq!(upload_ids(&report.aborted), vec!["up1", "up2"]);
+        assert!(report.failed.is_empty());
+        assert!(aborted_uris().is_empty());
+
+        let report = abort(false).await.unwrap();
+        assert!(!report.dry_run);
+        assert_eq!(upload_ids(&report.aborted), vec!["up1"]);
+        assert_eq!(report.failed.len(), 1);
+        let (upload, error) = &report.failed[0];
+        assert_eq!(upload.upload_id, "up2");
+        assert!(error.is_access_denied());
+        assert_eq!(
+            aborted_uris(),
+            vec!["/locked?uploadId=up2", "/stale?uploadId=up1"]
+        );
+    });
+}
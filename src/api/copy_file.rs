@@ -0,0 +1,231 @@
+//! This module contains an api to copy a file on the remote server ucloud.cn without
+//! downloading it.
+
+use std::collections::HashMap;
+
+use chrono::Local;
+use reqwest::{
+    Method,
+    header::{HeaderMap, HeaderName},
+};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation, MetadataDirective,
+        object::{CopyFileResponse, ObjectOptAuthParamBuilder, response_headers},
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(CopyFileOperation);
+
+define_api_request!(
+    CopyFileRequest,
+    CopyFileOperationBuilder,
+    CopyFileResponse,
+    validate,
+    {
+        /// Required: Destination bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Destination key name
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Required: Source bucket name
+        #[builder(setter(into))]
+        pub src_bucket_name: String,
+
+        /// Required: Source key name
+        #[builder(setter(into))]
+        pub src_key_name: String,
+
+        /// Optional: UNCHANGED（默认值）:复制源文件的用户自定义元数据。
+        ///
+        /// REPLACE：忽略源文件的用户自定义元数据，直接采用请求中指定的元数据。
+        #[builder(setter(into, strip_option), default)]
+        pub metadata_directive: Option<MetadataDirective>,
+
+        /// Optional: User custom headers metadata, used with `MetadataDirective::Replace`.
+        #[builder(setter(into, strip_option), default)]
+        pub metadata: Option<HashMap<String, String>>,
+
+        /// Optional: Storage type of the destination: STANDARD | IA | ARCHIVE
+        #[builder(setter(into, strip_option), default)]
+        pub storage_type: Option<String>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl CopyFileRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            self.metadata.as_ref().and_then(Option::as_ref),
+            [
+                (
+                    "X-Ufile-Storage-Class",
+                    self.storage_type.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
+/// Build the `X-Ufile-Copy-Source` header value of the given source object.
+///
+/// Every segment of the key is percent-encoded, the `/` separating them are kept.
+pub(crate) fn copy_source(bucket_name: &str, key_name: &str) -> String {
+    let key_name = key_name
+        .split('/')
+        .map(urlencoding::encode)
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("/{bucket_name}/{key_name}")
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for CopyFileOperation {
+    type Request = CopyFileRequest;
+    type Response = CopyFileResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let CopyFileRequest {
+            bucket_name,
+            key_name,
+            src_bucket_name,
+            src_key_name,
+            metadata_directive,
+            metadata,
+            storage_type,
+            security_token,
+            ..
+        } = req;
+        let copy_source = copy_source(src_bucket_name.as_str(), src_key_name.as_str());
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::PUT)
+                    .bucket(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .content_type("application/octet-stream")
+                    .date(date.as_str())
+                    .x_ufile_copy_source(copy_source.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", "application/octet-stream".parse()?);
+                headers.insert("Content-Length", "0".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                headers.insert("X-Ufile-Copy-Source", copy_source.parse()?);
+                if let Some(ref directive) = metadata_directive {
+                    headers.insert("X-Ufile-Metadata-Directive", directive.to_string().parse()?);
+                }
+                if let Some(ref storage_type) = storage_type
+                    && !storage_type.is_empty()
+                {
+                    headers.insert("X-Ufile-Storage-Class", storage_type.parse()?);
+                }
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                if let Some(ref metadata) = metadata {
+                    for (k, v) in metadata {
+                        headers.insert(
+                            format!("X-Ufile-Meta-{k}").parse::<HeaderName>()?,
+                            v.parse()?,
+                        );
+                    }
+                }
+                let url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), key_name.as_str());
+                Ok(self.client.get_client().put(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("Copy file response: {:?}", resp);
+        if resp.status().is_success() {
+            let headers = response_headers(resp.headers());
+            return Ok(CopyFileResponse {
+                session_id: headers.get("x-sessionid").cloned(),
+                etag: headers.get("etag").map(|v| v.trim_matches('"').to_string()),
+                headers,
+            });
+        }
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Failed to copy file: {} to {} with error: {:?}",
+            copy_source,
+            key_name,
+            error
+        );
+        Err(error)
+    }
+}
+
+#[test]
+fn test_copy_file_source_header() {
+    use crate::{
+        api::ApiRequest,
+        client::HttpClient,
+        util::test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        let (object_config, log) = serve(|_, _| {
+            (
+                200,
+                vec![
+                    ("ETag", "\"etag\"".to_string()),
+                    ("X-SessionId", "s1".to_string()),
+                ],
+                String::new(),
+            )
+        })
+        .await;
+        let response = CopyFileRequestBuilder::default()
+            .object_config(object_config)
+            .client(HttpClient::builder().build().unwrap())
+            .bucket_name("bucket")
+            .key_name("copy")
+            .src_bucket_name("source")
+            .src_key_name("dir/文件 1.txt")
+            .build()
+            .unwrap()
+            .request()
+            .await
+            .unwrap();
+        assert_eq!(response.etag.as_deref(), Some("etag"));
+        assert_eq!(response.session_id.as_deref(), Some("s1"));
+
+        let received = log.lock().unwrap()[0].clone();
+        assert_eq!(received.method, "PUT");
+        assert_eq!(received.uri, "/copy");
+        assert_eq!(
+            received.headers["x-ufile-copy-source"],
+            "/source/dir/%E6%96%87%E4%BB%B6%201.txt"
+        );
+        assert_eq!(copy_source("source", "key"), "/source/key");
+    });
+}
@@ -0,0 +1,168 @@
+//! This module contains an api to delete a file from the remote server ucloud.cn.
+
+use chrono::Local;
+use reqwest::{Method, StatusCode, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation,
+        object::{DeleteFileResponse, DeleteOutcome, ObjectOptAuthParamBuilder, response_headers},
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(DeleteFileOperation);
+
+define_api_request!(
+    DeleteFileRequest,
+    DeleteFileOperationBuilder,
+    DeleteFileResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Key name of the file or object name.
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Optional: `STS` temporary security token.
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl DeleteFileRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for DeleteFileOperation {
+    type Request = DeleteFileRequest;
+    type Response = DeleteFileResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let DeleteFileRequest {
+            bucket_name,
+            key_name,
+            security_token,
+            ..
+        } = req;
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::DELETE)
+                    .bucket(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .content_type("application/json")
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", "application/json".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), key_name.as_str());
+                Ok(self.client.get_client().delete(url).headers(headers))
+            })
+            .await?;
+        ::tracing::debug!("Delete file response: {:?}", resp);
+        let status = resp.status();
+        let headers = response_headers(resp.headers());
+        let outcome = if status.is_success() {
+            DeleteOutcome::Deleted
+        } else if status == StatusCode::NOT_FOUND {
+            // The object does not exist (anymore), which is not an error for delete.
+            DeleteOutcome::NotFound
+        } else {
+            let error = UFileError::from_response(resp).await;
+            tracing::error!(
+                "Failed to delete file: {} with error: {:?}",
+                key_name,
+                error
+            );
+            return Err(error);
+        };
+        Ok(DeleteFileResponse {
+            session_id: headers.get("x-sessionid").cloned(),
+            headers,
+            outcome,
+        })
+    }
+}
+
+#[test]
+fn test_delete_file_outcome() {
+    use crate::{
+        api::ApiRequest,
+        client::HttpClient,
+        util::test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        let (object_config, log) = serve(|_, received| match received.uri.as_str() {
+            "/deleted" => (204, vec![("X-SessionId", "s1".to_string())], String::new()),
+            "/gone" => (
+                404,
+                vec![],
+                r#"{"RetCode":-30010,"ErrMsg":"object not exist"}"#.to_string(),
+            ),
+            _ => (
+                403,
+                vec![],
+                r#"{"RetCode":-148653,"ErrMsg":"no permission"}"#.to_string(),
+            ),
+        })
+        .await;
+        let delete = |key_name: &str| {
+            DeleteFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name(key_name)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let response = delete("deleted").await.unwrap();
+        assert_eq!(response.outcome, DeleteOutcome::Deleted);
+        assert_eq!(response.session_id.as_deref(), Some("s1"));
+        assert_eq!(log.lock().unwrap()[0].method, "DELETE");
+
+        // A missing object is not an error for delete.
+        let response = delete("gone").await.unwrap();
+        assert_eq!(response.outcome, DeleteOutcome::NotFound);
+
+        let error = delete("denied").await.unwrap_err();
+        assert!(error.is_access_denied());
+        assert_eq!(error.ret_code(), Some(-148653));
+        assert_eq!(log.lock().unwrap().len(), 3);
+    });
+}
@@ -0,0 +1,146 @@
+//! This module contains the on-disk checkpoint used to resume chunked downloads.
+//!
+//! The checkpoint is stored in a sidecar file next to the destination and records the completed
+//! byte ranges together with the etag and size of the remote object. It is discarded if the
+//! remote object changed since the checkpoint was written.
+
+use std::{
+    fs, io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::UFileError, util::fs::write_atomically};
+
+/// Suffix of the sidecar checkpoint file.
+const CHECKPOINT_SUFFIX: &str = ".ufcheckpoint";
+
+/// Progress of a chunked download persisted to disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DownloadCheckpoint {
+    /// Bucket name of the remote object.
+    pub bucket: String,
+    /// Key name of the remote object.
+    pub key: String,
+    /// ETag of the remote object when the download started.
+    pub etag: Option<String>,
+    /// Size of the remote object.
+    pub size: u64,
+    /// Size of every chunk.
+    pub chunk_size: u64,
+    /// Sorted, merged and inclusive byte ranges which are written to the destination.
+    pub completed: Vec<(u64, u64)>,
+}
+
+impl DownloadCheckpoint {
+    /// Create an empty checkpoint of the remote object.
+    pub fn new(
+        bucket: impl Into<String>,
+        key: impl Into<String>,
+        etag: Option<String>,
+        size: u64,
+        chunk_size: u64,
+    ) -> Self {
+        Self {
+            bucket: bucket.into(),
+            key: key.into(),
+            etag,
+            size,
+            chunk_size,
+            completed: vec![],
+        }
+    }
+
+    /// Path of the sidecar checkpoint file of the destination.
+    pub fn path_for(dest: &Path) -> PathBuf {
+        let mut path = dest.as_os_str().to_owned();
+        path.push(CHECKPOINT_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// Load the checkpoint, returns `None` if the checkpoint file does not exist.
+    ///
+    /// A corrupt checkpoint file is removed and `None` is returned, so that the download starts
+    /// over instead of failing until the file is removed by hand.
+    pub fn load(path: &Path) -> Result<Option<Self>, UFileError> {
+        if !path.try_exists()? {
+            return Ok(None);
+        }
+        match serde_json::from_slice(&fs::read(path)?) {
+            Ok(checkpoint) => Ok(Some(checkpoint)),
+            Err(e) => {
+                tracing::warn!("Discard corrupt download checkpoint {:?}: {}", path, e);
+                fs::remove_file(path)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Save the checkpoint atomically by writing a temporary file, syncing and renaming it.
+    pub fn save(&self, path: &Path) -> Result<(), UFileError> {
+        write_atomically(path, &serde_json::to_vec(self).map_err(io::Error::from)?)?;
+        Ok(())
+    }
+
+    /// Whether the checkpoint was written for the same remote object and chunk layout.
+    pub fn is_resumable_for(&self, other: &DownloadCheckpoint) -> bool {
+        self.bucket == other.bucket
+            && self.key == other.key
+            && self.etag == other.etag
+            && self.size == other.size
+            && self.chunk_size == other.chunk_size
+    }
+
+    /// Whether the range is completely written.
+    pub fn is_completed(&self, range: &RangeInclusive<u64>) -> bool {
+        self.completed
+            .iter()
+            .any(|&(start, end)| start <= *range.start() && *range.end() <= end)
+    }
+
+    /// Mark the range as written, merging it with adjacent ranges.
+    pub fn mark_completed(&mut self, range: &RangeInclusive<u64>) {
+        self.completed.push((*range.start(), *range.end()));
+        self.completed.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.completed.len());
+        for &(start, end) in &self.completed {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.completed = merged;
+    }
+}
+
+#[test]
+fn test_download_checkpoint() {
+    let mut checkpoint = DownloadCheckpoint::new("bucket", "key", Some("etag".into()), 10, 4);
+    checkpoint.mark_completed(&(8..=9));
+    checkpoint.mark_completed(&(0..=3));
+    assert_eq!(checkpoint.completed, vec![(0, 3), (8, 9)]);
+    assert!(checkpoint.is_completed(&(0..=3)));
+    assert!(!checkpoint.is_completed(&(4..=7)));
+    checkpoint.mark_completed(&(4..=7));
+    assert_eq!(checkpoint.completed, vec![(0, 9)]);
+
+    let dir = crate::util::test_server::temp_dir("checkpoint");
+    let path = DownloadCheckpoint::path_for(&dir.join("file"));
+    checkpoint.save(&path).unwrap();
+    let loaded = DownloadCheckpoint::load(&path).unwrap().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, checkpoint);
+    assert!(DownloadCheckpoint::load(&path).unwrap().is_none());
+
+    // A checkpoint cut short by a crash is discarded.
+    fs::write(&path, r#"{"Bucket":"bucket","Key":"#).unwrap();
+    assert!(DownloadCheckpoint::load(&path).unwrap().is_none());
+    assert!(!path.exists());
+    fs::remove_dir_all(&dir).unwrap();
+
+    let changed = DownloadCheckpoint::new("bucket", "key", Some("changed".into()), 10, 4);
+    assert!(!loaded.is_resumable_for(&changed));
+}
@@ -1,211 +1,619 @@
 //! This modules contains an api to download a file from the remote server ucloud.cn.
+//!
+//! The file is split into chunks which are downloaded concurrently with ranged get object requests
+//! and written to the destination with positional writes. It does not depend on any async runtime.
+//! Positional writes are only supported on unix like systems, as the file utils of the crate.
+//! Resumable downloads persist the completed chunks to a sidecar [`DownloadCheckpoint`] file.
 
-use std::{ops::Range, path::PathBuf, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind},
+    ops::RangeInclusive,
+    os::unix::fs::FileExt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-use anyhow::{Error, anyhow};
-use derive_builder::Builder;
-use reqwest::header::HeaderMap;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use sha1::{Digest, Sha1};
 
-use crate::api::GenPrivateUrlRequestBuilder;
-use crate::constant::{self, DEFAULT_CONCURRENCY};
+use crate::api::validator::are_headers_valid;
 use crate::{
-    api::{ApiOperation, GenPrivateUrlOperation, ObjectConfig, Sealed, object::HeadFileResponse},
-    client::HttpClient,
+    api::{
+        ApiOperation, ApiRequest, ByteRange, DownloadCheckpoint, GetObjectOutput,
+        GetObjectRequestBuilder, HeadFileRequestBuilder, ProgressCallback, ProgressTracker,
+        object::{DownloadFileResponse, HeadFileResponse},
+    },
+    constant::{DEFAULT_CONCURRENCY, MULTIPART_SIZE},
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry,
+    util::{ETag, SHA1_DIGEST_LENGTH, split_ranges},
 };
 
-#[derive(Builder)]
-#[builder(pattern = "owned")]
-pub struct DownloadFileRequest {
-    /// Required: Bucket name
-    #[builder(setter(into))]
-    pub bucket_name: String,
+/// Completed chunks are saved to the checkpoint after this many chunks, or after
+/// [`CHECKPOINT_BATCH_INTERVAL`] since the last save.
+const CHECKPOINT_BATCH_CHUNKS: usize = 16;
 
-    /// Required: Key name or object name on ucloud.cn
-    #[builder(setter(into))]
-    pub key_name: String,
+/// Longest time completed chunks wait to be saved to the checkpoint.
+const CHECKPOINT_BATCH_INTERVAL: Duration = Duration::from_secs(5);
 
-    /// Optional: Concurrency of download file.
-    ///
-    /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
-    /// Default Chunk Size: 1024 * 1024 * 4 (4MB)
-    #[builder(setter(into, strip_option), default)]
-    pub concurrency: Option<u32>,
+define_operation_struct!(DownloadFileOperation);
 
-    /// Required: File profile response from head file api.
-    pub head: HeadFileResponse,
+define_api_request!(
+    DownloadFileRequest,
+    DownloadFileOperationBuilder,
+    DownloadFileResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
 
-    /// Required: The expires time of the private url.
-    /// Default: 86400 (1 day)
-    #[builder(default = "86400")]
-    pub expires: u64,
+        /// Required: Key name or object name on ucloud.cn
+        #[builder(setter(into))]
+        pub key_name: String,
 
-    /// Optional: The dest path to save the file.
-    #[builder(setter(into, strip_option), default)]
-    pub dest: Option<PathBuf>,
+        /// Optional: File profile response from head file api, requested if unset.
+        #[builder(setter(into, strip_option), default)]
+        pub head: Option<HeadFileResponse>,
 
-    /// Optional: Whether to overwrite the dest file if it already exists.
-    /// Default: true
-    #[builder(default = "true")]
-    pub overwrite: bool,
+        /// Optional: Number of chunks downloaded concurrently.
+        ///
+        /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
+        #[builder(setter(into, strip_option), default)]
+        pub concurrency: Option<usize>,
 
-    /// Optional: The iop cmd to download the file which are images.
-    ///
-    /// Default: None
-    #[builder(setter(into, strip_option), default)]
-    pub iop_cmd: Option<String>,
+        /// Optional: Size of every chunk in bytes.
+        ///
+        /// Default: 1024 * 1024 * 4 (4MB) from `crate::constant::MULTIPART_SIZE`
+        #[builder(setter(into, strip_option), default)]
+        pub chunk_size: Option<u64>,
 
-    /// Optional: `STS` temporay security token used to authenticate the request.
-    ///
-    /// Default: None
-    #[builder(setter(into, strip_option), default)]
-    pub security_token: Option<String>,
+        /// Optional: How many times a chunk whose body failed to be received is retried before
+        /// the download fails. Failed requests are retried by the retry policy of the client.
+        /// Default: 3
+        #[builder(default = "3")]
+        pub max_chunk_retries: usize,
+
+        /// Optional: The dest path to save the file, defaults to the key name.
+        #[builder(setter(into, strip_option), default)]
+        pub dest: Option<PathBuf>,
+
+        /// Optional: Whether to overwrite the dest file if it already exists.
+        /// Default: true
+        #[builder(default = "true")]
+        pub overwrite: bool,
+
+        /// Optional: Whether to persist the completed chunks to `<dest>.ufcheckpoint` and resume
+        /// from it on restart. The checkpoint is discarded if the remote object changed.
+        /// Default: false
+        #[builder(default)]
+        pub resumable: bool,
+
+        /// Optional: Whether to verify the downloaded file against the UFile ETag of the object.
+        /// Fails with [`UFileError::IntegrityMismatch`] if they differ.
+        /// Default: true
+        #[builder(default = "true")]
+        pub verify_etag: bool,
+
+        /// Optional: Called with the progress of the bytes received by all chunks, chunks
+        /// completed before a resume are counted as transferred.
+        #[builder(setter(into, strip_option), default)]
+        pub progress: Option<ProgressCallback>,
+
+        /// Optional: `STS` temporay security token used to authenticate the request.
+        ///
+        /// Default: None
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl DownloadFileRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
 }
 
-pub struct DownloadFileOperation {
-    client: HttpClient,
-    object_config: ObjectConfig,
+/// Parameters shared by all chunks of a download.
+struct ChunkContext<'a> {
+    file: &'a File,
+    bucket_name: &'a str,
+    key_name: &'a str,
+    security_token: Option<&'a str>,
+    /// How many times a failed chunk is retried.
+    max_retries: usize,
+    /// Whether the SHA1 of every `MULTIPART_SIZE` block is computed while the chunk is written,
+    /// chunks must then start at a block boundary.
+    hash_blocks: bool,
+}
+
+/// Whether downloading the chunk again may succeed. Requests are retried by the client with its
+/// retry policy, so a chunk only retries failures while receiving its body: the body stream
+/// failed or ended early. Unexpected responses, e.g. a range ignored by the server, repeat on
+/// every attempt and are not retried.
+fn is_retryable_chunk_error(error: &UFileError) -> bool {
+    match error {
+        UFileError::NetworkError(e) => e.is_body() || e.is_decode(),
+        UFileError::IncompleteBody { .. } => true,
+        _ => false,
+    }
 }
 
-#[allow(unused)]
 impl DownloadFileOperation {
-    pub fn new(object_config: ObjectConfig, client: HttpClient) -> Self {
-        Self {
-            object_config,
-            client,
+    /// Download the chunk with a ranged request and write it to the file at the chunk offset.
+    /// Returns the SHA1 of every block of the chunk if blocks are hashed.
+    ///
+    /// The bytes received are reported to the progress tracker as progress of the chunk index,
+    /// and discarded again if the chunk failed.
+    async fn download_chunk(
+        &self,
+        context: &ChunkContext<'_>,
+        range: &RangeInclusive<u64>,
+        progress: Option<(&ProgressTracker, usize)>,
+    ) -> Result<Vec<[u8; SHA1_DIGEST_LENGTH]>, UFileError> {
+        let output = GetObjectRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(context.bucket_name)
+            .key_name(context.key_name)
+            .range(ByteRange::Inclusive {
+                start: *range.start(),
+                end: *range.end(),
+            })
+            .security_token(context.security_token.map(ToString::to_string))
+            .build()?
+            .request()
+            .await?;
+        let mut response = match output {
+            // The whole object is returned if the range covers all of it.
+            GetObjectOutput::PartialContent(response) | GetObjectOutput::Full(response) => response,
+            output => {
+                return Err(UFileError::InvalidResponse(format!(
+                    "Unexpected response for range {range:?}: {output:?}"
+                )));
+            }
+        };
+        let mut offset = *range.start();
+        let mut block_hashes = Vec::new();
+        let mut block = Sha1::new();
+        let mut block_len = 0;
+        let result = async {
+            while let Some(bytes) = response.body.try_next().await? {
+                if offset + bytes.len() as u64 > range.end() + 1 {
+                    return Err(UFileError::InvalidResponse(format!(
+                        "Received more bytes than range {range:?}"
+                    )));
+                }
+                context.file.write_all_at(&bytes, offset).inspect_err(|e| {
+                    tracing::error!("Failed to write data at position {}: {}", offset, e);
+                })?;
+                offset += bytes.len() as u64;
+                if let Some((progress_tracker, chunk_index)) = progress {
+                    progress_tracker.advance(bytes.len() as u64, Some(chunk_index));
+                }
+                if context.hash_blocks {
+                    let mut data = &bytes[..];
+                    while !data.is_empty() {
+                        let len = data.len().min(MULTIPART_SIZE as usize - block_len);
+                        block.update(&data[..len]);
+                        block_len += len;
+                        data = &data[len..];
+                        if block_len == MULTIPART_SIZE as usize {
+                            block_hashes.push(block.finalize_reset().into());
+                            block_len = 0;
+                        }
+                    }
+                }
+            }
+            if offset != range.end() + 1 {
+                return Err(UFileError::IncompleteBody {
+                    expected: range.end() - range.start() + 1,
+                    received: offset - range.start(),
+                });
+            }
+            if block_len > 0 {
+                block_hashes.push(block.finalize().into());
+            }
+            Ok(block_hashes)
+        }
+        .await;
+        if result.is_err()
+            && let Some((progress_tracker, chunk_index)) = progress
+        {
+            progress_tracker.rewind(offset - range.start(), Some(chunk_index));
         }
+        result
     }
-}
 
-impl Sealed for DownloadFileOperation {}
+    /// Download the chunk, retry it individually with backoff if receiving its body failed.
+    /// Returns the downloaded range and the SHA1 of its blocks.
+    async fn download_chunk_with_retry(
+        &self,
+        context: &ChunkContext<'_>,
+        range: RangeInclusive<u64>,
+        progress: Option<(&ProgressTracker, usize)>,
+    ) -> Result<(RangeInclusive<u64>, Vec<[u8; SHA1_DIGEST_LENGTH]>), UFileError> {
+        let mut attempt = 0;
+        loop {
+            match self.download_chunk(context, &range, progress).await {
+                Ok(block_hashes) => return Ok((range, block_hashes)),
+                Err(e) if attempt < context.max_retries && is_retryable_chunk_error(&e) => {
+                    attempt += 1;
+                    let backoff = self.client.retry_policy().backoff(attempt as u32);
+                    tracing::warn!(
+                        "Failed to download chunk {:?}, retry {}/{} in {:?}: {:?}",
+                        range,
+                        attempt,
+                        context.max_retries,
+                        backoff,
+                        e
+                    );
+                    retry::sleep(backoff).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
 
 #[async_trait::async_trait]
 impl ApiOperation for DownloadFileOperation {
     type Request = DownloadFileRequest;
-    type Response = ();
-    type Error = Error;
+    type Response = DownloadFileResponse;
+    type Error = UFileError;
 
     async fn execute(&self, request: Self::Request) -> Result<Self::Response, Self::Error> {
         let DownloadFileRequest {
             bucket_name,
             key_name,
-            concurrency,
             head,
-            expires,
+            concurrency,
+            chunk_size,
+            max_chunk_retries,
             dest,
             overwrite,
-            iop_cmd,
+            resumable,
+            verify_etag,
+            progress,
             security_token,
+            ..
         } = request;
+        let head = match head {
+            Some(head) => head,
+            None => {
+                HeadFileRequestBuilder::default()
+                    .object_config(self.object_config.clone())
+                    .client(self.client.clone())
+                    .bucket_name(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .security_token(security_token.clone())
+                    .build()?
+                    .request()
+                    .await?
+            }
+        };
         let total_file_size = head.content_length;
-        // Calculate the chunks count will be downloaded.
-        let chunk_count = (total_file_size + constant::MULTIPART_SIZE as u64 - 1)
-            .div_ceil(constant::MULTIPART_SIZE as u64);
-        // Separate file into chunks, considering the last chunk might be smaller than MULTIPART_SIZE
-        let ranges = (0..chunk_count)
-            .map(|i| {
-                let start = i * constant::MULTIPART_SIZE as u64;
-                let end = ((i + 1) * constant::MULTIPART_SIZE as u64).min(total_file_size);
-                Range { start, end }
-            })
-            .collect::<Vec<_>>();
-        // Download the file chunks concurrently and write to the dest file.
-        let concurrency = if let Some(concurrency) = concurrency {
-            concurrency as usize
+        let chunk_size = chunk_size.unwrap_or(MULTIPART_SIZE as u64);
+        let concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        let etag = head
+            .etag
+            .map(|etag| etag.trim_matches(|c| c == '\"' || c == '\'').to_string());
+
+        // Determie destination path
+        let dest_path = dest.unwrap_or_else(|| PathBuf::from(key_name.as_str()));
+        let checkpoint_path = DownloadCheckpoint::path_for(&dest_path);
+        let fresh_checkpoint = DownloadCheckpoint::new(
+            bucket_name.as_str(),
+            key_name.as_str(),
+            etag.clone(),
+            total_file_size,
+            chunk_size,
+        );
+
+        // Resume only if the checkpoint was written for the same remote object.
+        let resumed_checkpoint = if resumable {
+            match DownloadCheckpoint::load(&checkpoint_path)? {
+                Some(checkpoint)
+                    if checkpoint.is_resumable_for(&fresh_checkpoint)
+                        && dest_path.try_exists()? =>
+                {
+                    tracing::info!(
+                        "Resume download of {} to {:?}, completed ranges: {:?}",
+                        key_name,
+                        dest_path,
+                        checkpoint.completed
+                    );
+                    Some(checkpoint)
+                }
+                Some(_) => {
+                    tracing::info!("Discard outdated checkpoint {:?}", checkpoint_path);
+                    fs::remove_file(&checkpoint_path)?;
+                    None
+                }
+                None => None,
+            }
         } else {
-            DEFAULT_CONCURRENCY
+            None
         };
-        let semphore = Arc::new(Semaphore::new(concurrency));
-        let mut join_handles = vec![];
-        // create handles with chunk count iterator.
-        let gen_private_url_req = GenPrivateUrlRequestBuilder::default()
-            .key_name(key_name.as_str())
-            .bucket_name(bucket_name.as_str())
-            .expires(expires)
-            .build()?;
-        let mut gen_private_url_operation = GenPrivateUrlOperation::new(self.object_config.clone());
-        let download_url = gen_private_url_operation
-            .execute(gen_private_url_req)
-            .await?;
 
-        // Determie destination path
-        let dest_path = if let Some(ref dest) = dest {
-            dest.clone()
+        // The file is also read to hash the blocks of chunks completed before a resume.
+        let file = if resumed_checkpoint.is_some() {
+            OpenOptions::new().read(true).write(true).open(&dest_path)?
         } else {
-            PathBuf::from(key_name.as_str())
+            // Check if file exists and handle overwrite.
+            if dest_path.try_exists()? && !overwrite {
+                return Err(io::Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("File {dest_path:?} already exists. Set overwrite=true to replace it."),
+                )
+                .into());
+            }
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&dest_path)?
         };
+        // The output file has its final size, chunks are written at their offsets.
+        file.set_len(total_file_size)?;
 
-        // Check if file exists and handle overwrite.
-        if fs::try_exists(&dest_path).await? && !overwrite {
-            return Err(anyhow!(
-                "File {:?} already exists. Set overwrite=true to replace it.",
-                dest_path
-            ));
+        let mut checkpoint = resumed_checkpoint.unwrap_or(fresh_checkpoint);
+        let ranges = split_ranges(total_file_size, chunk_size)?
+            .into_iter()
+            .filter(|range| !checkpoint.is_completed(range))
+            .collect::<Vec<_>>();
+        let progress_tracker = progress.map(|callback| {
+            let progress_tracker = ProgressTracker::new(Some(total_file_size), callback);
+            let remaining = ranges
+                .iter()
+                .map(|range| range.end() - range.start() + 1)
+                .sum::<u64>();
+            progress_tracker.resume(total_file_size - remaining);
+            progress_tracker
+        });
+
+        // Blocks are hashed while they are written, unless chunks do not start at a block
+        // boundary and the file is hashed after it is downloaded.
+        let block_size = MULTIPART_SIZE as u64;
+        let hash_blocks = verify_etag && etag.is_some() && chunk_size % block_size == 0;
+        let context = ChunkContext {
+            file: &file,
+            bucket_name: bucket_name.as_str(),
+            key_name: key_name.as_str(),
+            security_token: security_token.as_deref(),
+            max_retries: max_chunk_retries,
+            hash_blocks,
+        };
+        let mut block_hashes = BTreeMap::new();
+        let mut chunks = stream::iter(ranges)
+            .map(|range| {
+                let progress = progress_tracker.as_ref().map(|progress_tracker| {
+                    (progress_tracker, (range.start() / chunk_size) as usize)
+                });
+                self.download_chunk_with_retry(&context, range, progress)
+            })
+            .buffer_unordered(concurrency);
+        // Make sure the chunks are on disk before they are recorded as completed.
+        let save_checkpoint = |checkpoint: &DownloadCheckpoint| {
+            file.sync_data()?;
+            checkpoint.save(&checkpoint_path)
+        };
+        // The checkpoint is saved in batches to keep the fsync off the path of every chunk.
+        let mut unsaved_chunks = 0;
+        let mut last_save = Instant::now();
+        let result = loop {
+            match chunks.next().await {
+                Some(Ok((range, hashes))) => {
+                    block_hashes.extend((range.start() / block_size..).zip(hashes));
+                    if !resumable {
+                        continue;
+                    }
+                    checkpoint.mark_completed(&range);
+                    unsaved_chunks += 1;
+                    if unsaved_chunks >= CHECKPOINT_BATCH_CHUNKS
+                        || last_save.elapsed() >= CHECKPOINT_BATCH_INTERVAL
+                    {
+                        save_checkpoint(&checkpoint)?;
+                        unsaved_chunks = 0;
+                        last_save = Instant::now();
+                    }
+                }
+                Some(Err(e)) => break Err(e),
+                None => break Ok(()),
+            }
+        };
+        drop(chunks);
+        // Keep the chunks completed before a failure for the next attempt, the checkpoint is
+        // removed after a successful download anyway.
+        if resumable
+            && unsaved_chunks > 0
+            && result.is_err()
+            && let Err(e) = save_checkpoint(&checkpoint)
+        {
+            tracing::warn!("Failed to save checkpoint {:?}: {:?}", checkpoint_path, e);
         }
-        // Create the output file
-        let file = fs::File::create(&dest_path).await?;
-        let file = Arc::new(sync::Mutex::new(file));
-
-        for range in ranges {
-            let semphore = Arc::clone(&semphore);
-            let url = download_url.clone();
-            let security_token = security_token.clone();
-            let file = Arc::clone(&file);
-            let client = self.client.clone();
-
-            let join_handle = tokio::spawn(async move {
-                // Acquire a semaphore permit before download the chunk.
-                let _permit = semphore.acquire().await.unwrap();
-                // Download the chunk.
-                let mut headers = HeaderMap::new();
-                // create http headers
-                headers.insert(
-                    "Range",
-                    format!("bytes={}-{}", range.start, range.end)
-                        .parse()
-                        .unwrap(),
+        result?;
+        file.sync_all()?;
+
+        if verify_etag && let Some(ref expected) = etag {
+            let actual = if hash_blocks {
+                // Blocks of chunks completed before a resume are read back from the file.
+                let mut buffer = Vec::new();
+                let hashes = (0..total_file_size.div_ceil(block_size))
+                    .map(|index| match block_hashes.remove(&index) {
+                        Some(hash) => Ok(hash),
+                        None => {
+                            let start = index * block_size;
+                            buffer.resize(block_size.min(total_file_size - start) as usize, 0);
+                            file.read_exact_at(&mut buffer, start)?;
+                            Ok(Sha1::digest(&buffer).into())
+                        }
+                    })
+                    .collect::<Result<Vec<_>, UFileError>>()?;
+                ETag::from_block_hashes(&hashes).etag
+            } else {
+                ETag::from_file(&dest_path, MULTIPART_SIZE)?.etag
+            };
+            if &actual != expected {
+                tracing::error!(
+                    "Downloaded file {:?} does not match etag, expected: {}, actual: {}",
+                    dest_path,
+                    expected,
+                    actual
                 );
-                if let Some(ref security_token) = security_token
-                    && !security_token.is_empty()
-                {
-                    headers.insert("SecurityToken", security_token.parse().unwrap());
+                if resumable {
+                    fs::remove_file(&checkpoint_path)?;
                 }
-                let response = client
-                    .get_client()
-                    .get(url)
-                    .headers(headers)
-                    .send()
-                    .await
-                    .map_err(|e| anyhow!("Request fialed: {}", e))?;
-                if !response.status().is_success() {
-                    return Err(anyhow!(
-                        "Downlaod failed with status: {}",
-                        response.status()
-                    ));
-                }
-
-                // Read response body.
-                let data = response
-                    .bytes()
-                    .await
-                    .map_err(|e| anyhow!("Failed to read response body: {}", e))?;
-
-                // Write to file at correct offset.
-                let mut file = file.lock().await;
-                file.seek(io::SeekFrom::Start(range.start))
-                    .await
-                    .map_err(|e| anyhow!("Failed to seek to position {}: {}", range.start, e))?;
-                file.write_all(&data)
-                    .await
-                    .map_err(|e| anyhow!("Failed to write data to file: {}", e))?;
-                Ok(())
-            });
-            join_handles.push(join_handle);
+                return Err(UFileError::IntegrityMismatch {
+                    key: key_name,
+                    part_number: None,
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
         }
-
-        // Wait for all chunks to complete
-        for handle in join_handles {
-            handle.await??;
+        if resumable && checkpoint_path.try_exists()? {
+            fs::remove_file(&checkpoint_path)?;
         }
-        Ok(())
+        Ok(DownloadFileResponse {
+            path: dest_path,
+            size: total_file_size,
+            etag,
+        })
     }
 }
+
+#[test]
+fn test_download_file_chunks() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use crate::{client::HttpClient, retry::RetryPolicy, util::test_server};
+
+    test_server::block_on(async {
+        let block_size = MULTIPART_SIZE as usize;
+        let data = (0..2 * block_size + 5)
+            .map(|i| (b'a' + (i % 26) as u8) as char)
+            .collect::<String>();
+        let etag = ETag::from_bytes(data.as_bytes()).etag;
+        // The second chunk fails once with 503 and once with a truncated body, or with 404 while
+        // `fail_tail` is set.
+        let flaky = Arc::new(AtomicUsize::new(2));
+        let fail_tail = Arc::new(AtomicBool::new(false));
+        let (server_data, server_fail_tail) = (data.clone(), fail_tail.clone());
+        let (object_config, log) = test_server::serve(move |_, received| {
+            let (start, end) = received.headers["range"]
+                .trim_start_matches("bytes=")
+                .split_once('-')
+                .map(|(start, end)| {
+                    (
+                        start.parse::<usize>().unwrap(),
+                        end.parse::<usize>().unwrap(),
+                    )
+                })
+                .unwrap();
+            if received.uri.contains("ignored") {
+                return (200, vec![], server_data.clone());
+            }
+            if received.uri.contains("missing")
+                || (start > 0 && server_fail_tail.load(Ordering::SeqCst))
+            {
+                return (404, vec![], String::new());
+            }
+            let content_range = format!("bytes {start}-{end}/{}", server_data.len());
+            if start > 0 {
+                match flaky.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)) {
+                    Ok(2) => return (503, vec![], String::new()),
+                    Ok(_) => {
+                        let truncated = server_data[start..end].to_string();
+                        return (206, vec![("Content-Range", content_range)], truncated);
+                    }
+                    Err(_) => {}
+                }
+            }
+            (
+                206,
+                vec![("Content-Range", content_range)],
+                server_data[start..=end].to_string(),
+            )
+        })
+        .await;
+        let client = HttpClient::builder()
+            .build()
+            .unwrap()
+            .with_retry_policy(RetryPolicy {
+                initial_backoff: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            });
+        let dir = test_server::temp_dir("download");
+        let dest = dir.join("file");
+        let download = |key: &str, chunk_size: u64| {
+            DownloadFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(client.clone())
+                .bucket_name("bucket")
+                .key_name(key)
+                .head(HeadFileResponse {
+                    headers: None,
+                    etag: Some(etag.clone()),
+                    content_type: "text/plain".to_string(),
+                    content_length: (2 * block_size + 5) as u64,
+                    last_modified: None,
+                })
+                .chunk_size(chunk_size)
+                .concurrency(1usize)
+                .resumable(true)
+                .dest(dest.clone())
+                .build()
+                .unwrap()
+                .request()
+        };
+
+        // Blocks are hashed while written. The 503 of the second chunk is retried by the client,
+        // its truncated body by the chunk.
+        download("file", 2 * block_size as u64).await.unwrap();
+        assert_eq!(log.lock().unwrap().len(), 4);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), data);
+
+        // Chunks not aligned to blocks are verified by hashing the file.
+        log.lock().unwrap().clear();
+        download("file", 3 << 20).await.unwrap();
+        assert_eq!(log.lock().unwrap().len(), 3);
+
+        // A missing object is not retried.
+        log.lock().unwrap().clear();
+        let error = download("missing", block_size as u64).await.unwrap_err();
+        assert!(error.is_not_found());
+        assert_eq!(log.lock().unwrap().len(), 1);
+
+        // The resumed download hashes the block of the chunk completed before from the file.
+        fail_tail.store(true, Ordering::SeqCst);
+        assert!(download("file", block_size as u64).await.is_err());
+        fail_tail.store(false, Ordering::SeqCst);
+        log.lock().unwrap().clear();
+        download("file", block_size as u64).await.unwrap();
+        assert_eq!(log.lock().unwrap().len(), 2);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), data);
+        assert!(!DownloadCheckpoint::path_for(&dest).exists());
+
+        // The whole object sent by a server ignoring the range is not retried.
+        log.lock().unwrap().clear();
+        let error = download("ignored", block_size as u64).await.unwrap_err();
+        assert!(matches!(error, UFileError::InvalidResponse(_)));
+        assert_eq!(log.lock().unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    });
+}
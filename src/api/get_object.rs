@@ -0,0 +1,343 @@
+//! This module contains an api to get the content of a file from the remote server ucloud.cn
+//! as a stream of bytes, without depending on any async runtime.
+
+use chrono::Local;
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::{Method, StatusCode, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation,
+        object::{
+            ByteRange, ContentRange, GetObjectOutput, GetObjectResponse, ObjectOptAuthParamBuilder,
+            response_headers,
+        },
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(GetObjectOperation);
+
+define_api_request!(
+    GetObjectRequest,
+    GetObjectOperationBuilder,
+    GetObjectOutput,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Key name of the file or object name.
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Optional: Only get the given byte range of the object.
+        #[builder(setter(into, strip_option), default)]
+        pub range: Option<ByteRange>,
+
+        /// Optional: Only return the object if its etag matches, otherwise `PreconditionFailed`.
+        #[builder(setter(into, strip_option), default)]
+        pub if_match: Option<String>,
+
+        /// Optional: Only return the object if its etag does not match, otherwise `NotModified`.
+        #[builder(setter(into, strip_option), default)]
+        pub if_none_match: Option<String>,
+
+        /// Optional: Only return the object if it is modified since the http date,
+        /// otherwise `NotModified`.
+        #[builder(setter(into, strip_option), default)]
+        pub if_modified_since: Option<String>,
+
+        /// Optional: Only return the object if it is not modified since the http date,
+        /// otherwise `PreconditionFailed`.
+        #[builder(setter(into, strip_option), default)]
+        pub if_unmodified_since: Option<String>,
+
+        /// Optional: The iop cmd to process the file which are images.
+        #[builder(setter(into, strip_option), default)]
+        pub iop_cmd: Option<String>,
+
+        /// Optional: `STS` temporary security token.
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl GetObjectRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [
+                (
+                    "If-Match",
+                    self.if_match.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "If-None-Match",
+                    self.if_none_match.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "If-Modified-Since",
+                    self.if_modified_since.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "If-Unmodified-Since",
+                    self.if_unmodified_since.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for GetObjectOperation {
+    type Request = GetObjectRequest;
+    type Response = GetObjectOutput;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let GetObjectRequest {
+            bucket_name,
+            key_name,
+            range,
+            if_match,
+            if_none_match,
+            if_modified_since,
+            if_unmodified_since,
+            iop_cmd,
+            security_token,
+            ..
+        } = req;
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::GET)
+                    .bucket(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                if let Some(range) = range {
+                    headers.insert("Range", range.to_string().parse()?);
+                }
+                if let Some(ref if_match) = if_match {
+                    headers.insert("If-Match", if_match.parse()?);
+                }
+                if let Some(ref if_none_match) = if_none_match {
+                    headers.insert("If-None-Match", if_none_match.parse()?);
+                }
+                if let Some(ref if_modified_since) = if_modified_since {
+                    headers.insert("If-Modified-Since", if_modified_since.parse()?);
+                }
+                if let Some(ref if_unmodified_since) = if_unmodified_since {
+                    headers.insert("If-Unmodified-Since", if_unmodified_since.parse()?);
+                }
+                let mut url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), key_name.as_str());
+                if let Some(ref iop_cmd) = iop_cmd {
+                    url = format!("{url}?iopcmd={}", urlencoding::encode(iop_cmd.as_str()));
+                }
+                Ok(self.client.get_client().get(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("Get object response: {:?}", resp);
+        let status = resp.status();
+        let headers = response_headers(resp.headers());
+        match status {
+            StatusCode::NOT_MODIFIED => return Ok(GetObjectOutput::NotModified(headers)),
+            StatusCode::PRECONDITION_FAILED => {
+                return Ok(GetObjectOutput::PreconditionFailed(headers));
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                return Ok(GetObjectOutput::RangeNotSatisfiable(headers));
+            }
+            _ => {}
+        }
+        if status.is_success() {
+            let response = GetObjectResponse {
+                content_type: headers.get("content-type").cloned(),
+                content_length: resp.content_length(),
+                etag: headers.get("etag").map(|v| v.trim_matches('"').to_string()),
+                last_modified: headers.get("last-modified").cloned(),
+                content_range: headers
+                    .get("content-range")
+                    .and_then(|v| ContentRange::parse(v)),
+                headers,
+                body: resp.bytes_stream().map_err(UFileError::from).boxed(),
+            };
+            return Ok(if status == StatusCode::PARTIAL_CONTENT {
+                GetObjectOutput::PartialContent(response)
+            } else {
+                GetObjectOutput::Full(response)
+            });
+        }
+        let error = UFileError::from_response(resp).await;
+        tracing::error!("Failed to get object: {} with error: {:?}", key_name, error);
+        Err(error)
+    }
+}
+
+#[test]
+fn test_get_object_request_validation() {
+    use crate::{api::ObjectConfigBuilder, client::HttpClient};
+
+    let object_config = ObjectConfigBuilder::default()
+        .public_key("public")
+        .private_key("private")
+        .region("test")
+        .build()
+        .unwrap();
+    let builder = || {
+        GetObjectRequestBuilder::default()
+            .object_config(object_config.clone())
+            .client(HttpClient::builder().build().unwrap())
+            .bucket_name("bucket")
+            .key_name("key")
+    };
+    let value = |value: &str| Some(value.to_string());
+    assert!(builder().if_match(value("\"etag\"")).build().is_ok());
+    // Invalid header values fail to build instead of failing every attempt to send.
+    assert!(builder().if_match(value("a\nb")).build().is_err());
+    assert!(
+        builder()
+            .if_modified_since(value("\u{7f}"))
+            .build()
+            .is_err()
+    );
+    assert!(builder().security_token(value("a\rb")).build().is_err());
+}
+
+#[test]
+fn test_get_object_outcomes() {
+    use crate::{api::ApiRequest, client::HttpClient, util::test_server};
+
+    test_server::block_on(async {
+        // The object "0123456789" with etag "etag", ranges are given as `bytes=start-end`.
+        let (object_config, log) = test_server::serve(|_, received| {
+            let etag = vec![("ETag", "\"etag\"".to_string())];
+            let header = |name: &str| received.headers.get(name).map(String::as_str);
+            if header("if-none-match") == Some("\"etag\"") {
+                return (304, etag, String::new());
+            }
+            if header("if-match").is_some_and(|value| value != "\"etag\"") {
+                return (412, etag, String::new());
+            }
+            let Some(range) = header("range") else {
+                return (200, etag, "0123456789".to_string());
+            };
+            let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+            let (start, end) = (
+                start.parse::<usize>().unwrap(),
+                end.parse::<usize>().unwrap(),
+            );
+            if start >= 10 {
+                return (
+                    416,
+                    vec![("Content-Range", "bytes */10".to_string())],
+                    String::new(),
+                );
+            }
+            let end = end.min(9);
+            let mut headers = etag;
+            headers.push(("Content-Range", format!("bytes {start}-{end}/10")));
+            (206, headers, "0123456789"[start..=end].to_string())
+        })
+        .await;
+        let builder = || {
+            GetObjectRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name("key")
+        };
+        let read = |response: GetObjectResponse| async move {
+            response
+                .body
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap()
+                .concat()
+        };
+
+        let output = builder().build().unwrap().request().await.unwrap();
+        let GetObjectOutput::Full(response) = output else {
+            panic!("expect the whole object, got {output:?}");
+        };
+        assert_eq!(response.etag.as_deref(), Some("etag"));
+        assert_eq!(read(response).await, b"0123456789");
+
+        let output = builder()
+            .range(ByteRange::Inclusive { start: 2, end: 5 })
+            .build()
+            .unwrap()
+            .request()
+            .await
+            .unwrap();
+        let GetObjectOutput::PartialContent(response) = output else {
+            panic!("expect partial content, got {output:?}");
+        };
+        assert_eq!(
+            response.content_range,
+            Some(ContentRange {
+                start: 2,
+                end: 5,
+                total: Some(10)
+            })
+        );
+        assert_eq!(read(response).await, b"2345");
+        assert_eq!(log.lock().unwrap()[1].headers["range"], "bytes=2-5");
+
+        let output = builder()
+            .if_none_match("\"etag\"".to_string())
+            .build()
+            .unwrap()
+            .request()
+            .await
+            .unwrap();
+        assert!(matches!(output, GetObjectOutput::NotModified(_)));
+        assert_eq!(output.headers()["etag"], "\"etag\"");
+
+        let output = builder()
+            .if_match("\"other\"".to_string())
+            .build()
+            .unwrap()
+            .request()
+            .await
+            .unwrap();
+        assert!(matches!(output, GetObjectOutput::PreconditionFailed(_)));
+
+        let output = builder()
+            .range(ByteRange::Inclusive { start: 20, end: 29 })
+            .build()
+            .unwrap()
+            .request()
+            .await
+            .unwrap();
+        assert!(matches!(output, GetObjectOutput::RangeNotSatisfiable(_)));
+        assert!(output.into_response().is_none());
+    });
+}
@@ -0,0 +1,206 @@
+//! This module contains an api to list the in-progress multipart uploads of a bucket on the
+//! remote server ucloud.cn, e.g. to find the uploads orphaned by a crashed process.
+
+use chrono::Local;
+use reqwest::{Method, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation,
+        object::{ListMultipartUploadsResponse, ObjectOptAuthParamBuilder, response_headers},
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(ListMultipartUploadsOperation);
+
+define_api_request!(
+    ListMultipartUploadsRequest,
+    ListMultipartUploadsOperationBuilder,
+    ListMultipartUploadsResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Optional: Only list uploads of keys beginning with the prefix.
+        #[builder(setter(into, strip_option), default)]
+        pub prefix: Option<String>,
+
+        /// Optional: List uploads after the marker, use `next_marker` of the previous page to continue.
+        #[builder(setter(into, strip_option), default)]
+        pub marker: Option<String>,
+
+        /// Optional: Max uploads of one page.
+        #[builder(setter(into, strip_option), default)]
+        pub limit: Option<u32>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl ListMultipartUploadsRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for ListMultipartUploadsOperation {
+    type Request = ListMultipartUploadsRequest;
+    type Response = ListMultipartUploadsResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let ListMultipartUploadsRequest {
+            bucket_name,
+            prefix,
+            marker,
+            limit,
+            security_token,
+            ..
+        } = req;
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::GET)
+                    .bucket(bucket_name.as_str())
+                    .key_name("")
+                    .content_type("application/json")
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", "application/json".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let mut url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), "");
+                url.push_str("?muploadid");
+                if let Some(ref prefix) = prefix {
+                    url.push_str(&format!("&prefix={}", urlencoding::encode(prefix)));
+                }
+                if let Some(ref marker) = marker {
+                    url.push_str(&format!("&marker={}", urlencoding::encode(marker)));
+                }
+                if let Some(limit) = limit {
+                    url.push_str(&format!("&limit={limit}"));
+                }
+                Ok(self.client.get_client().get(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("List multipart uploads response: {:?}", resp);
+        if resp.status().is_success() {
+            let headers = response_headers(resp.headers());
+            let mut body: ListMultipartUploadsResponse = resp.json().await?;
+            body.headers = headers;
+            for upload in body
+                .uploads
+                .iter_mut()
+                .filter(|upload| upload.bucket.is_empty())
+            {
+                upload.bucket = bucket_name.clone();
+            }
+            return Ok(body);
+        }
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Failed to list multipart uploads of bucket: {} with error: {:?}",
+            bucket_name,
+            error
+        );
+        Err(error)
+    }
+}
+
+#[test]
+fn test_list_multipart_uploads_pages() {
+    use crate::{
+        api::ApiRequest,
+        client::HttpClient,
+        util::test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        // One upload per page, the second page is the last one.
+        let (object_config, log) = serve(|_, received| {
+            let body = if received.uri.contains("marker=") {
+                r#"{"RetCode":0,"Prefix":"backup/","NextMarker":"","DataSet":[
+                    {"UploadId":"up2","FileName":"backup/b","Bucket":"other"}]}"#
+            } else {
+                r#"{"RetCode":0,"Prefix":"backup/","NextMarker":"backup/a up1","DataSet":[
+                    {"UploadId":"up1","FileName":"backup/a","StartTime":1700000000}]}"#
+            };
+            (200, vec![], body.to_string())
+        })
+        .await;
+        let mut uploads = Vec::new();
+        let mut marker = None;
+        loop {
+            let page = ListMultipartUploadsRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .prefix("backup/".to_string())
+                .marker(marker)
+                .limit(1u32)
+                .build()
+                .unwrap()
+                .request()
+                .await
+                .unwrap();
+            uploads.extend(page.uploads);
+            marker = page.next_marker.filter(|marker| !marker.is_empty());
+            if marker.is_none() {
+                break;
+            }
+        }
+        // The bucket of an upload defaults to the listed bucket.
+        assert_eq!(
+            uploads
+                .iter()
+                .map(|upload| (upload.upload_id.as_str(), upload.bucket.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("up1", "bucket"), ("up2", "other")]
+        );
+        assert_eq!(uploads[0].start_time, Some(1700000000));
+        let uris = log
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|received| received.uri.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            uris,
+            vec![
+                "/?muploadid&prefix=backup%2F&limit=1",
+                "/?muploadid&prefix=backup%2F&marker=backup%2Fa%20up1&limit=1",
+            ]
+        );
+    });
+}
@@ -0,0 +1,297 @@
+//! This module contains an api to list objects of a bucket on the remote server ucloud.cn.
+
+use chrono::Local;
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use reqwest::{Method, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation, ApiRequest, ObjectConfig,
+        object::{ListObjectsResponse, ObjectOptAuthParamBuilder, ObjectSummary, response_headers},
+    },
+    client::HttpClient,
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(ListObjectsOperation);
+
+define_api_request!(
+    ListObjectsRequest,
+    ListObjectsOperationBuilder,
+    ListObjectsResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Optional: Only list keys beginning with the prefix.
+        #[builder(setter(into, strip_option), default)]
+        pub prefix: Option<String>,
+
+        /// Optional: Group keys sharing the same part between the prefix and the delimiter
+        /// into common prefixes, eg: `/` to list a directory.
+        #[builder(setter(into, strip_option), default)]
+        pub delimiter: Option<String>,
+
+        /// Optional: List keys after the marker, use `next_marker` of the previous page to continue.
+        #[builder(setter(into, strip_option), default)]
+        pub marker: Option<String>,
+
+        /// Optional: Max keys of one page.
+        #[builder(setter(into, strip_option), default)]
+        pub max_keys: Option<u32>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl ListObjectsRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for ListObjectsOperation {
+    type Request = ListObjectsRequest;
+    type Response = ListObjectsResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let ListObjectsRequest {
+            bucket_name,
+            prefix,
+            delimiter,
+            marker,
+            max_keys,
+            security_token,
+            ..
+        } = req;
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::GET)
+                    .bucket(bucket_name.as_str())
+                    .key_name("")
+                    .content_type("application/json")
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", "application/json".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let mut url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), "");
+                url.push_str("?listobjects");
+                if let Some(ref prefix) = prefix {
+                    url.push_str(&format!("&prefix={}", urlencoding::encode(prefix)));
+                }
+                if let Some(ref delimiter) = delimiter {
+                    url.push_str(&format!("&delimiter={}", urlencoding::encode(delimiter)));
+                }
+                if let Some(ref marker) = marker {
+                    url.push_str(&format!("&marker={}", urlencoding::encode(marker)));
+                }
+                if let Some(max_keys) = max_keys {
+                    url.push_str(&format!("&max-keys={max_keys}"));
+                }
+                Ok(self.client.get_client().get(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("List objects response: {:?}", resp);
+        if resp.status().is_success() {
+            let headers = response_headers(resp.headers());
+            let mut body: ListObjectsResponse = resp.json().await?;
+            body.headers = headers;
+            return Ok(body);
+        }
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Failed to list objects of bucket: {} with error: {:?}",
+            bucket_name,
+            error
+        );
+        Err(error)
+    }
+}
+
+/// Options of the auto-paginating list objects stream.
+#[derive(Debug, Clone, Default)]
+pub struct ListObjectsStreamOptions {
+    /// Optional: Max keys requested per page, use the server default if unset.
+    pub page_size: Option<u32>,
+
+    /// Optional: Stop after yielding this many objects in total.
+    pub max_items: Option<usize>,
+
+    /// Optional: Security token
+    pub security_token: Option<String>,
+}
+
+/// Pagination state of the list objects stream.
+enum PageState {
+    Next(Option<String>),
+    Done,
+}
+
+/// Create a stream of every object under `prefix`, requesting the next page with the
+/// next marker transparently.
+pub(crate) fn list_objects_stream(
+    object_config: ObjectConfig,
+    client: HttpClient,
+    bucket_name: String,
+    prefix: String,
+    options: ListObjectsStreamOptions,
+) -> impl Stream<Item = Result<ObjectSummary, UFileError>> + Send + 'static {
+    let ListObjectsStreamOptions {
+        page_size,
+        max_items,
+        security_token,
+    } = options;
+    stream::try_unfold(PageState::Next(None), move |state| {
+        let page_request = match state {
+            PageState::Next(marker) => Some((
+                ListObjectsRequestBuilder::default()
+                    .object_config(object_config.clone())
+                    .client(client.clone())
+                    .bucket_name(bucket_name.as_str())
+                    .prefix(prefix.clone())
+                    .marker(marker.clone())
+                    .max_keys(page_size)
+                    .security_token(security_token.clone())
+                    .build(),
+                marker,
+            )),
+            PageState::Done => None,
+        };
+        let bucket_name = bucket_name.clone();
+        async move {
+            let Some((request, marker)) = page_request else {
+                return Ok::<_, UFileError>(None);
+            };
+            let page = request?.request().await?;
+            // Fall back to the last key if the server does not return a next marker.
+            let next_marker = page
+                .next_marker
+                .filter(|next_marker| !next_marker.is_empty())
+                .or_else(|| page.contents.last().map(|object| object.key.clone()));
+            let next_state = match next_marker {
+                _ if !page.is_truncated => PageState::Done,
+                Some(next_marker) if Some(&next_marker) != marker.as_ref() => {
+                    PageState::Next(Some(next_marker))
+                }
+                // The listing can not continue, ending here would look like a complete listing.
+                next_marker => {
+                    return Err(UFileError::InvalidResponse(format!(
+                        "list objects of {} is truncated after marker {:?}, but the next marker {:?} does not advance",
+                        bucket_name, marker, next_marker
+                    )));
+                }
+            };
+            Ok(Some((
+                stream::iter(page.contents.into_iter().map(Ok::<_, UFileError>)),
+                next_state,
+            )))
+        }
+    })
+    .try_flatten()
+    .take(max_items.unwrap_or(usize::MAX))
+}
+
+#[test]
+fn test_list_objects_stream() {
+    use crate::util::test_server::{block_on, serve};
+
+    block_on(async {
+        // Three pages of two keys, the second page only returns the last key as marker.
+        let (object_config, log) = serve(|_, received| {
+            let page = match received
+                .uri
+                .split("marker=")
+                .nth(1)
+                .and_then(|marker| marker.split('&').next())
+            {
+                None => {
+                    r#"{"IsTruncated":true,"NextMarker":"b","Contents":[{"Key":"a"},{"Key":"b"}]}"#
+                }
+                Some("b") => r#"{"IsTruncated":true,"Contents":[{"Key":"c"},{"Key":"d"}]}"#,
+                Some("d") => r#"{"IsTruncated":false,"Contents":[{"Key":"e"},{"Key":"f"}]}"#,
+                Some(_) => r#"{"IsTruncated":true,"NextMarker":"x","Contents":[]}"#,
+            };
+            (200, vec![], page.to_string())
+        })
+        .await;
+        let client = HttpClient::builder().build().unwrap();
+        let keys = |max_items| {
+            list_objects_stream(
+                object_config.clone(),
+                client.clone(),
+                "bucket".to_string(),
+                "".to_string(),
+                ListObjectsStreamOptions {
+                    page_size: Some(2),
+                    max_items,
+                    security_token: None,
+                },
+            )
+            .map_ok(|object| object.key)
+            .try_collect::<Vec<_>>()
+        };
+        assert_eq!(keys(None).await.unwrap(), ["a", "b", "c", "d", "e", "f"]);
+        assert_eq!(log.lock().unwrap().len(), 3);
+        assert!(log.lock().unwrap()[0].uri.contains("max-keys=2"));
+
+        // The stream stops requesting pages once the cap is reached.
+        log.lock().unwrap().clear();
+        assert_eq!(keys(Some(3)).await.unwrap(), ["a", "b", "c"]);
+        assert_eq!(log.lock().unwrap().len(), 2);
+    });
+
+    block_on(async {
+        // A truncated page whose next marker does not advance.
+        let (object_config, _) = serve(|_, _| {
+            (
+                200,
+                vec![],
+                r#"{"IsTruncated":true,"NextMarker":"","Contents":[]}"#.to_string(),
+            )
+        })
+        .await;
+        let result = list_objects_stream(
+            object_config,
+            HttpClient::builder().build().unwrap(),
+            "bucket".to_string(),
+            "".to_string(),
+            ListObjectsStreamOptions::default(),
+        )
+        .try_collect::<Vec<_>>()
+        .await;
+        assert!(result.is_err());
+    });
+}
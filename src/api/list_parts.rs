@@ -0,0 +1,199 @@
+//! This module contains an api to list the uploaded parts of an in-progress multipart upload on
+//! the remote server ucloud.cn.
+
+use chrono::Local;
+use reqwest::{Method, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation,
+        object::{ListPartsResponse, ObjectOptAuthParamBuilder, response_headers},
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(ListPartsOperation);
+
+define_api_request!(
+    ListPartsRequest,
+    ListPartsOperationBuilder,
+    ListPartsResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: 上传 ID
+        #[builder(setter(into))]
+        pub upload_id: String,
+
+        /// Optional: Max parts of one page.
+        #[builder(setter(into, strip_option), default)]
+        pub max_parts: Option<u32>,
+
+        /// Optional: List parts after the part number, use `next_part_number_marker` of the
+        /// previous page to continue.
+        #[builder(setter(into, strip_option), default)]
+        pub part_number_marker: Option<u64>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl ListPartsRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for ListPartsOperation {
+    type Request = ListPartsRequest;
+    type Response = ListPartsResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let ListPartsRequest {
+            bucket_name,
+            upload_id,
+            max_parts,
+            part_number_marker,
+            security_token,
+            ..
+        } = req;
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::GET)
+                    .bucket(bucket_name.as_str())
+                    .key_name("")
+                    .content_type("application/json")
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", "application/json".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let mut url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), "");
+                url.push_str(&format!(
+                    "?muploadpart&uploadId={}",
+                    urlencoding::encode(&upload_id)
+                ));
+                if let Some(max_parts) = max_parts {
+                    url.push_str(&format!("&max-parts={max_parts}"));
+                }
+                if let Some(part_number_marker) = part_number_marker {
+                    url.push_str(&format!("&part-number-marker={part_number_marker}"));
+                }
+                Ok(self.client.get_client().get(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("List parts response: {:?}", resp);
+        if resp.status().is_success() {
+            let headers = response_headers(resp.headers());
+            let mut body: ListPartsResponse = resp.json().await?;
+            body.headers = headers;
+            return Ok(body);
+        }
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Failed to list parts of upload: {} with error: {:?}",
+            upload_id,
+            error
+        );
+        Err(error)
+    }
+}
+
+#[test]
+fn test_list_parts_pages() {
+    use crate::{
+        api::ApiRequest,
+        client::HttpClient,
+        util::test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        // Two parts per page, the second page is the last one.
+        let (object_config, log) = serve(|_, received| {
+            let body = if received.uri.contains("part-number-marker=1") {
+                r#"{"RetCode":0,"UploadId":"up/1","IsTruncated":false,"Parts":[
+                    {"PartNumber":2,"Size":"2","ETag":"\"e2\""}]}"#
+            } else {
+                r#"{"RetCode":0,"UploadId":"up/1","IsTruncated":true,"NextPartNumberMarker":"1",
+                    "Parts":[{"PartNumber":0,"Size":4,"ETag":"\"e0\""},
+                    {"PartNumber":1,"Size":4,"ETag":"\"e1\""}]}"#
+            };
+            (200, vec![], body.to_string())
+        })
+        .await;
+        let mut part_states = Vec::new();
+        let mut part_number_marker = None;
+        loop {
+            let page = ListPartsRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .upload_id("up/1")
+                .max_parts(2u32)
+                .part_number_marker(part_number_marker)
+                .build()
+                .unwrap()
+                .request()
+                .await
+                .unwrap();
+            part_states.extend(page.part_states());
+            if !page.is_truncated {
+                break;
+            }
+            part_number_marker = page.next_part_number_marker;
+        }
+        assert_eq!(
+            part_states
+                .iter()
+                .map(|part_state| (part_state.part_number, part_state.etag.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(0, "e0"), (1, "e1"), (2, "e2")]
+        );
+        let uris = log
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|received| received.uri.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            uris,
+            vec![
+                "/?muploadpart&uploadId=up%2F1&max-parts=2",
+                "/?muploadpart&uploadId=up%2F1&max-parts=2&part-number-marker=1",
+            ]
+        );
+    });
+}
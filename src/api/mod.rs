@@ -1,15 +1,33 @@
-// Download module will be implemented in the future.
-// This crate does not want to depend on tokio.
-// mod download_file;
+mod abort_stale_uploads;
+mod copy_file;
+mod delete_file;
+#[cfg(feature = "download")]
+mod download_checkpoint;
+#[cfg(feature = "download")]
+mod download_file;
+mod get_object;
 mod head_file;
+mod list_multipart_uploads;
+mod list_objects;
+mod list_parts;
 mod multipart_abort;
+mod multipart_copy;
 mod multipart_file;
 mod multipart_finish;
 mod multipart_init;
+mod multipart_upload_parts;
 mod object;
+mod progress;
 mod put_file;
+mod rename_file;
+mod resume_upload;
 mod stream;
 mod traits;
+mod upload_file;
+mod upload_hit;
+mod upload_journal;
+mod upload_part_copy;
+mod upload_stream;
 mod util;
 mod validator;
 
@@ -19,32 +37,89 @@ pub use util::*;
 /// Re-export Sealed trait
 pub(crate) use traits::sealed::Sealed;
 
+pub(crate) use stream::buffer_pool;
 /// Re-export PrgressStream
-pub use stream::{ByteStream, ProgressStream};
+pub use stream::{ByteStream, ByteStreamReader, ProgressStream};
 
 /// Re-export configuration for s3 credential
 pub use object::*;
 
+/// Re-export abort_stale_uploads module
+pub use abort_stale_uploads::*;
+
+/// Re-export copy_file module
+pub use copy_file::*;
+
+/// Re-export delete_file module
+pub use delete_file::*;
+
+/// Re-export get_object module
+pub use get_object::*;
+
 /// Re-export head_file module
 pub use head_file::*;
 
+/// Re-export list_multipart_uploads module
+pub use list_multipart_uploads::*;
+
+/// Re-export list_objects module
+pub use list_objects::*;
+
+/// Re-export list_parts module
+pub use list_parts::*;
+
 /// Re-export multipart_file module
 pub use multipart_file::*;
 
 /// Re-export multipart_init module
 pub use multipart_init::*;
 
+/// Re-export multipart_upload_parts module
+pub use multipart_upload_parts::*;
+
 /// Re-export trait module
 pub use traits::{ApiOperation, ApiRequest};
 
 // Re-export multipart_abort module
 pub use multipart_abort::*;
 
+/// Re-export progress module
+pub use progress::*;
+
 /// Re-export put_file module
 pub use put_file::*;
 
-// Re-export download_file module
-// pub use download_file::{DownloadFileOperation, DownloadFileRequest, DownloadFileRequestBuilder};
+/// Re-export rename_file module
+pub use rename_file::*;
+
+/// Re-export resume_upload module
+pub use resume_upload::*;
+
+/// Re-export download_file module
+#[cfg(feature = "download")]
+pub use download_file::*;
+
+/// Re-export download_checkpoint module
+#[cfg(feature = "download")]
+pub use download_checkpoint::DownloadCheckpoint;
 
 /// Re-export multipart_finish module
 pub use multipart_finish::*;
+
+/// Re-export upload_file module
+pub use upload_file::*;
+
+/// Re-export upload_hit module
+pub use upload_hit::*;
+
+/// Re-export upload_journal module
+pub use upload_journal::{UploadJournal, UploadedPart};
+
+/// Re-export upload_part_copy module
+pub use upload_part_copy::*;
+
+/// Re-export upload_stream module
+pub use upload_stream::*;
+
+/// Re-export multipart_copy module
+pub use multipart_copy::*;
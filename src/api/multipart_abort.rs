@@ -1,19 +1,18 @@
 use std::collections::HashMap;
 
-use anyhow::Error;
 use chrono::Local;
 use reqwest::{
     Method,
     header::{HeaderMap, HeaderName},
 };
 
+use crate::api::validator::are_headers_valid;
 use crate::{
     AuthorizationService,
-    api::{
-        ApiOperation, ObjectOptAuthParamBuilder,
-        object::{BaseResponse, InitMultipartState},
-    },
+    api::{ApiOperation, ObjectOptAuthParamBuilder, object::InitMultipartState},
     define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
 };
 
 define_operation_struct!(MultipartAbortOperation);
@@ -22,6 +21,7 @@ define_api_request!(
     MultipartAbortRequest,
     MultipartAbortOperationBuilder,
     (),
+    validate,
     {
         /// Required: State of multipart upload task.
         pub state: InitMultipartState,
@@ -36,11 +36,24 @@ define_api_request!(
     }
 );
 
+impl MultipartAbortRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            self.metadata.as_ref().and_then(Option::as_ref),
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
 #[async_trait::async_trait]
 impl ApiOperation for MultipartAbortOperation {
     type Request = MultipartAbortRequest;
     type Response = ();
-    type Error = Error;
+    type Error = UFileError;
 
     async fn execute(&self, request: Self::Request) -> Result<Self::Response, Self::Error> {
         let MultipartAbortRequest {
@@ -52,54 +65,54 @@ impl ApiOperation for MultipartAbortOperation {
         let mime_type = state
             .mime_type
             .clone()
-            .ok_or(Error::msg("mime type is unset."))?;
+            .ok_or_else(|| UFileError::InvalidRequest("mime type is unset.".to_string()))?;
         // let mime_type = "text/plain".to_string();
-        let date = Local::now().format("%Y%m%d%H%M%S").to_string();
-        let auth_object = ObjectOptAuthParamBuilder::default()
-            .method(Method::DELETE)
-            .bucket(state.bucket.as_str())
-            .key_name(state.key_name.as_str())
-            .content_type(mime_type.as_str())
-            .date(date.as_str())
-            .build()?;
-        let authorization =
-            AuthorizationService.authorization(auth_object, self.object_config.clone())?;
-        let mut headers = HeaderMap::new();
-        headers.insert("Content-Type", mime_type.parse().unwrap());
-        headers.insert("Accept", "*/*".parse().unwrap());
-        headers.insert("Date", date.parse().unwrap());
-        headers.insert("Authorization", authorization.parse().unwrap());
-        if let Some(ref security_token) = security_token
-            && !security_token.is_empty()
-        {
-            headers.insert("SecurityToken", security_token.parse().unwrap());
-        }
-        // We must add metadata to headers if metadata is not empty.
-        let url = self
-            .object_config
-            .generate_final_host(state.bucket.as_str(), state.key_name.as_str());
-        let url = format!("{}?uploadId={}", url, state.upload_id,);
-        if let Some(ref metadata) = metadata {
-            for (k, v) in metadata {
-                headers.insert(
-                    format!("X-Ufile-Meta-{k}").parse::<HeaderName>().unwrap(),
-                    v.parse().unwrap(),
-                );
-            }
-        }
         let resp = self
             .client
-            .get_client()
-            .delete(url)
-            .headers(headers)
-            .send()
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::DELETE)
+                    .bucket(state.bucket.as_str())
+                    .key_name(state.key_name.as_str())
+                    .content_type(mime_type.as_str())
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", mime_type.parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                // We must add metadata to headers if metadata is not empty.
+                let url = self
+                    .object_config
+                    .generate_final_host(state.bucket.as_str(), state.key_name.as_str());
+                let url = format!("{}?uploadId={}", url, state.upload_id,);
+                if let Some(ref metadata) = metadata {
+                    for (k, v) in metadata {
+                        headers.insert(
+                            format!("X-Ufile-Meta-{k}").parse::<HeaderName>()?,
+                            v.parse()?,
+                        );
+                    }
+                }
+                Ok(self.client.get_client().delete(url).headers(headers))
+            })
             .await?;
         tracing::info!("Abort multipart upload task: {:?}", resp);
         if resp.status().is_success() {
             return Ok(());
         }
-        let base_response: BaseResponse = resp.json().await?;
-        tracing::error!("Finish multipart upload task failed: {:?}", base_response);
-        Err(Error::msg("Failed to finish multipart upload task."))
+        let error = UFileError::from_response(resp).await;
+        tracing::error!("Finish multipart upload task failed: {:?}", error);
+        Err(error)
     }
 }
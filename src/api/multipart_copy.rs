@@ -0,0 +1,337 @@
+//! This module used to copy a large object server side with a multipart upload task.
+//!
+//! The source object is split into parts of the block size returned by the init request, every part
+//! is copied with `X-Ufile-Copy-Source-Range`, and the task is finished with the collected etags.
+//! The data never leaves ucloud.cn.
+
+use std::collections::HashMap;
+
+use futures_util::{StreamExt, TryStreamExt, stream};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    api::{
+        ApiOperation, ApiRequest, HeadFileRequestBuilder, MetadataDirective,
+        MultipartAbortRequestBuilder, MultipartFinishRequestBuilder, MultipartInitRequestBuilder,
+        UploadPartCopyRequestBuilder,
+        object::{FinishUploadResponse, InitMultipartState, MultipartUploadState},
+    },
+    constant::DEFAULT_CONCURRENCY,
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    util::split_ranges,
+};
+
+define_operation_struct!(MultipartCopyOperation);
+
+define_api_request!(
+    MultipartCopyRequest,
+    MultipartCopyOperationBuilder,
+    FinishUploadResponse,
+    validate,
+    {
+        /// Required: Destination bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Destination key name
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Required: Source bucket name
+        #[builder(setter(into))]
+        pub src_bucket_name: String,
+
+        /// Required: Source key name
+        #[builder(setter(into))]
+        pub src_key_name: String,
+
+        /// Optional: Content type of the destination, defaults to the content type of the source.
+        #[builder(setter(into, strip_option), default)]
+        pub mime_type: Option<String>,
+
+        /// Optional: Number of parts copied concurrently.
+        ///
+        /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
+        #[builder(setter(into, strip_option), default)]
+        pub concurrency: Option<usize>,
+
+        /// Optional: UNCHANGED（默认值）:保持初始化时设置的用户自定义元数据不变。
+        ///
+        /// REPLACE：忽略初始化分片时设置的用户自定义元数据，直接采用Finish请求中指定的元数据。
+        #[builder(setter(into, strip_option), default)]
+        pub metadata_directive: Option<MetadataDirective>,
+
+        /// Optional: User custom headers metadata of the destination.
+        #[builder(setter(into, strip_option), default)]
+        pub metadata: Option<HashMap<String, String>>,
+
+        /// Optional: Storage type of the destination: STANDARD | IA | ARCHIVE
+        #[builder(setter(into, strip_option), default)]
+        pub storage_type: Option<String>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl MultipartCopyRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            self.mime_type.as_ref().and_then(Option::as_deref),
+            self.metadata.as_ref().and_then(Option::as_ref),
+            [
+                (
+                    "X-Ufile-Storage-Class",
+                    self.storage_type.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
+impl MultipartCopyOperation {
+    /// Copy every part of the source object into the initialized task.
+    async fn copy_parts(
+        &self,
+        state: &InitMultipartState,
+        src_bucket_name: &str,
+        src_key_name: &str,
+        content_length: u64,
+        concurrency: usize,
+        security_token: Option<&str>,
+    ) -> Result<Vec<MultipartUploadState>, UFileError> {
+        let ranges = split_ranges(content_length, state.blk_size)?;
+        stream::iter(ranges.into_iter().enumerate())
+            .map(|(part_index, range)| {
+                let builder = UploadPartCopyRequestBuilder::default()
+                    .object_config(self.object_config.clone())
+                    .client(self.client.clone())
+                    .state(state.clone())
+                    .part_index(part_index)
+                    .src_bucket_name(src_bucket_name)
+                    .src_key_name(src_key_name)
+                    .range(range)
+                    .security_token(security_token.map(ToString::to_string));
+                async move { builder.build()?.request().await }
+            })
+            .buffer_unordered(concurrency)
+            .try_collect()
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for MultipartCopyOperation {
+    type Request = MultipartCopyRequest;
+    type Response = FinishUploadResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let MultipartCopyRequest {
+            bucket_name,
+            key_name,
+            src_bucket_name,
+            src_key_name,
+            mime_type,
+            concurrency,
+            metadata_directive,
+            metadata,
+            storage_type,
+            security_token,
+            ..
+        } = req;
+        // Get the size and content type of the source object.
+        let head = HeadFileRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(src_bucket_name.as_str())
+            .key_name(src_key_name.as_str())
+            .security_token(security_token.clone())
+            .build()?
+            .request()
+            .await?;
+        if head.content_length == 0 {
+            return Err(UFileError::InvalidRequest(
+                "Multipart copy of an empty object is not supported, use copy object instead."
+                    .to_string(),
+            ));
+        }
+
+        // Create the multipart upload task on the destination.
+        let state = MultipartInitRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(bucket_name.as_str())
+            .key_name(key_name.as_str())
+            .mime_type(mime_type.unwrap_or(head.content_type))
+            .storage_type(storage_type)
+            .security_token(security_token.clone())
+            .build()?
+            .request()
+            .await?;
+
+        let result = self
+            .copy_parts(
+                &state,
+                src_bucket_name.as_str(),
+                src_key_name.as_str(),
+                head.content_length,
+                concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1),
+                security_token.as_deref(),
+            )
+            .await;
+        let result = match result {
+            Ok(part_states) => match MultipartFinishRequestBuilder::default()
+                .object_config(self.object_config.clone())
+                .client(self.client.clone())
+                .state(state.clone())
+                .part_states(part_states)
+                .metadata_directive(metadata_directive)
+                .metadata(metadata)
+                .security_token(security_token.clone())
+                .build()
+            {
+                Ok(request) => request.request().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        if let Err(ref e) = result {
+            tracing::error!(
+                "Multipart copy of /{}/{} failed, abort upload {}: {:?}",
+                src_bucket_name,
+                src_key_name,
+                state.upload_id,
+                e
+            );
+            let abort_result = match MultipartAbortRequestBuilder::default()
+                .object_config(self.object_config.clone())
+                .client(self.client.clone())
+                .state(state)
+                .security_token(security_token)
+                .build()
+            {
+                Ok(request) => request.request().await,
+                Err(e) => Err(e),
+            };
+            // The copy failure is returned, not the failure to abort.
+            if let Err(abort_error) = abort_result {
+                tracing::error!("Failed to abort multipart copy: {:?}", abort_error);
+            }
+        }
+        result
+    }
+}
+
+#[test]
+fn test_multipart_copy() {
+    use crate::{
+        client::HttpClient,
+        util::test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        let (object_config, log) = serve(|_, received| {
+            let key = received.uri[1..].split('?').next().unwrap().to_string();
+            match received.method.as_str() {
+                "HEAD" => (
+                    200,
+                    vec![("Content-Type", "text/plain".to_string())],
+                    "0123456789".to_string(),
+                ),
+                "POST" if received.uri.ends_with("?uploads") => {
+                    let body = format!(
+                        r#"{{"UploadId":"{key}-upload","BlkSize":4,"Bucket":"bucket","Key":"{key}"}}"#
+                    );
+                    (200, vec![], body)
+                }
+                "PUT" => {
+                    let part_number = received.uri.split("partNumber=").nth(1).unwrap();
+                    if key == "broken" && part_number == "1" {
+                        let body = r#"{"RetCode":-1,"ErrMsg":"copy failed"}"#;
+                        (400, vec![], body.to_string())
+                    } else {
+                        (
+                            200,
+                            vec![("ETag", format!("\"etag-{part_number}\""))],
+                            String::new(),
+                        )
+                    }
+                }
+                "POST" => (
+                    200,
+                    vec![("ETag", "\"etag\"".to_string())],
+                    format!(r#"{{"Bucket":"bucket","Key":"{key}","FileSize":10}}"#),
+                ),
+                _ => (200, vec![], String::new()),
+            }
+        })
+        .await;
+        let copy = |key_name: &str| {
+            MultipartCopyRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name(key_name)
+                .src_bucket_name("source")
+                .src_key_name("src")
+                .build()
+                .unwrap()
+                .request()
+        };
+        let requests = || std::mem::take(&mut *log.lock().unwrap());
+
+        let response = copy("copy").await.unwrap();
+        assert_eq!(response.key, "copy");
+        assert_eq!(response.etag, "\"etag\"");
+        let mut requests_of_copy = requests();
+        assert_eq!(requests_of_copy.len(), 6);
+        assert_eq!(requests_of_copy.remove(0).method, "HEAD");
+        assert_eq!(requests_of_copy.remove(0).uri, "/copy?uploads");
+        let finish = requests_of_copy.pop().unwrap();
+        assert_eq!(finish.uri, "/copy?uploadId=copy-upload&newKey=");
+        assert_eq!(finish.body, b"etag-0,etag-1,etag-2");
+        let mut parts = requests_of_copy
+            .iter()
+            .map(|received| {
+                assert_eq!(received.headers["x-ufile-copy-source"], "/source/src");
+                (
+                    received.uri.as_str(),
+                    received.headers["x-ufile-copy-source-range"].as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        parts.sort();
+        assert_eq!(
+            parts,
+            vec![
+                ("/copy?uploadId=copy-upload&partNumber=0", "bytes=0-3"),
+                ("/copy?uploadId=copy-upload&partNumber=1", "bytes=4-7"),
+                ("/copy?uploadId=copy-upload&partNumber=2", "bytes=8-9"),
+            ]
+        );
+
+        // The part failure is returned and the upload is aborted instead of finished.
+        let error = copy("broken").await.unwrap_err();
+        assert_eq!(error.message(), Some("copy failed"));
+        let requests_of_copy = requests();
+        // Parts in flight when the part failed may arrive after the abort.
+        let abort = requests_of_copy
+            .iter()
+            .find(|received| received.method == "DELETE")
+            .unwrap();
+        assert_eq!(abort.uri, "/broken?uploadId=broken-upload");
+        assert!(
+            !requests_of_copy
+                .iter()
+                .any(|received| received.uri.contains("newKey="))
+        );
+    });
+}
@@ -1,18 +1,30 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
+use crate::api::validator::are_headers_valid;
 use crate::{
     AuthorizationService,
-    api::{ObjectOptAuthParamBuilder, traits::ApiOperation},
+    api::{
+        ByteStream, ObjectOptAuthParamBuilder, ProgressStream, ProgressTracker,
+        traits::ApiOperation,
+    },
     define_api_request,
+    retry::RetryMode,
 };
-use anyhow::Error;
 use bytes::Bytes;
 use chrono::Local;
-use reqwest::{Method, header::HeaderMap};
+use reqwest::{Body, Method, header::HeaderMap};
 
 use crate::{
     api::object::{InitMultipartState, MultipartUploadState},
     define_operation_struct,
+    error::UFileError,
+    util::{ETag, digest::content_md5 as content_md5_of},
 };
 
 define_operation_struct!(MultipartFileOperation);
@@ -20,16 +32,14 @@ define_api_request!(
     MultipartFileRequest,
     MultipartFileOperationBuilder,
     MultipartUploadState,
+    validate,
     {
         /// Required: Slice initial state
         pub state: InitMultipartState,
 
-        /// Required: Slice data
+        /// Required: Slice data, its length is the size of the slice.
         pub buffer: Bytes,
 
-        /// Required: Slice data size
-        pub buffer_size: u64,
-
         /// Required: Index of slices
         pub part_index: usize,
 
@@ -37,57 +47,75 @@ define_api_request!(
         #[builder(setter(into, strip_option), default)]
         pub content_md5: Option<String>,
 
+        /// Optional: Compute and send `Content-MD5` of the slice if `content_md5` is unset.
+        /// Default: false
+        #[builder(default)]
+        pub auto_content_md5: bool,
+
+        /// Optional: Verify the etag returned by ucloud.cn against the SHA1 of the slice.
+        /// Fails with [`UFileError::IntegrityMismatch`] if they differ.
+        /// Default: false
+        #[builder(default)]
+        pub verify_etag: bool,
+
+        /// Optional: Report the bytes sent to the tracker as progress of the part, the tracker
+        /// is usually shared by all parts of the upload.
+        #[builder(setter(into, strip_option), default)]
+        pub progress_tracker: Option<Arc<ProgressTracker>>,
+
         ///  Optional: temporary `STS` token
         #[builder(setter(into, strip_option), default)]
         pub security_token: Option<String>,
     }
 );
 
+impl MultipartFileRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [
+                (
+                    "Content-MD5",
+                    self.content_md5.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
 #[async_trait::async_trait]
 impl ApiOperation for MultipartFileOperation {
     type Request = MultipartFileRequest;
     type Response = MultipartUploadState;
-    type Error = Error;
+    type Error = UFileError;
 
-    async fn execute(&self, request: Self::Request) -> Result<MultipartUploadState, Error> {
+    async fn execute(&self, request: Self::Request) -> Result<MultipartUploadState, UFileError> {
         let MultipartFileRequest {
             state,
             buffer,
             part_index,
             content_md5,
+            auto_content_md5,
+            verify_etag,
+            progress_tracker,
             security_token,
             ..
         } = request;
-        let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+        let content_md5 = match content_md5 {
+            None if auto_content_md5 => Some(content_md5_of(&buffer)),
+            content_md5 => content_md5,
+        };
+        // The slice is moved into the request body, hash it before sending.
+        let expected_etag = verify_etag.then(|| ETag::from_bytes(&buffer).etag);
         let mime_type = state
             .mime_type
             .clone()
-            .ok_or(Error::msg("mime type is unset."))?;
-        let auth_object = ObjectOptAuthParamBuilder::default()
-            .method(Method::PUT)
-            .bucket(state.bucket.as_str())
-            .key_name(state.key_name.as_str())
-            .content_type(mime_type.as_str())
-            .date(date.as_str())
-            .content_md5(content_md5.clone().unwrap_or_default())
-            .build()?;
-        let authorization =
-            AuthorizationService.authorization(auth_object, self.object_config.clone())?;
-        let mut headers = HeaderMap::new();
-        headers.insert("Content-Type", mime_type.parse().unwrap());
-        headers.insert("Accept", "*/*".parse().unwrap());
-        headers.insert("Date", date.parse().unwrap());
-        headers.insert("Authorization", authorization.parse().unwrap());
-        headers.insert("Content-Length", buffer.len().to_string().parse().unwrap());
-        if let Some(content_md5) = content_md5 {
-            headers.insert("Content-MD5", content_md5.parse().unwrap());
-        }
-
-        if let Some(ref security_token) = security_token
-            && !security_token.is_empty()
-        {
-            headers.insert("SecurityToken", security_token.parse().unwrap());
-        }
+            .ok_or_else(|| UFileError::InvalidRequest("mime type is unset.".to_string()))?;
         // We must add metadata to headers if metadata is not empty.
         let url = self
             .object_config
@@ -96,13 +124,58 @@ impl ApiOperation for MultipartFileOperation {
             "{url}?uploadId={}&partNumber={}",
             state.upload_id, part_index
         );
+        // Bytes reported to the progress tracker by the previous attempt.
+        let mut sent: Option<Arc<AtomicUsize>> = None;
         let resp = self
             .client
-            .get_client()
-            .put(url)
-            .headers(headers)
-            .body(buffer.to_vec())
-            .send()
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::PUT)
+                    .bucket(state.bucket.as_str())
+                    .key_name(state.key_name.as_str())
+                    .content_type(mime_type.as_str())
+                    .date(date.as_str())
+                    .content_md5(content_md5.clone().unwrap_or_default())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", mime_type.parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                headers.insert("Content-Length", buffer.len().to_string().parse()?);
+                if let Some(ref content_md5) = content_md5 {
+                    headers.insert("Content-MD5", content_md5.parse()?);
+                }
+
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let body = match progress_tracker {
+                    Some(ref progress_tracker) => {
+                        if let Some(sent) = sent.take() {
+                            progress_tracker
+                                .rewind(sent.load(Ordering::Relaxed) as u64, Some(part_index));
+                        }
+                        let stream = ProgressStream::from(ByteStream::from_bytes(buffer.clone()))
+                            .with_progress_tracker(progress_tracker.clone(), Some(part_index));
+                        sent = Some(stream.progress_counter());
+                        Body::wrap_stream(stream)
+                    }
+                    None => Body::from(buffer.clone()),
+                };
+                Ok(self
+                    .client
+                    .get_client()
+                    .put(url.as_str())
+                    .headers(headers)
+                    .body(body))
+            })
             .await?;
         tracing::debug!("Upload part file response: {resp:?}");
         if resp.status().is_success() {
@@ -122,12 +195,95 @@ impl ApiOperation for MultipartFileOperation {
                 // get etag and set back to response.
                 body.etag = remove_quotes(etag).to_string();
             }
+            if let Some(expected) = expected_etag
+                && !etag_matches(&expected, &body.etag)
+            {
+                tracing::error!(
+                    "Upload part {} of {} etag mismatch, expected: {}, actual: {}",
+                    part_index,
+                    state.key_name,
+                    expected,
+                    body.etag
+                );
+                return Err(UFileError::IntegrityMismatch {
+                    key: state.key_name,
+                    part_number: Some(part_index),
+                    expected,
+                    actual: body.etag,
+                });
+            }
             return Ok(body);
         }
-        Err(Error::msg("Failed to upload part file"))
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Upload part {} of {} failed: {:?}",
+            part_index,
+            state.key_name,
+            error
+        );
+        Err(error)
     }
 }
 
 fn remove_quotes(s: &str) -> String {
     s.trim_matches(|c| c == '\"' || c == '\'').to_string()
 }
+
+/// Whether the part etag matches, ucloud.cn might return the bare SHA1 of a single block part.
+fn etag_matches(expected: &str, actual: &str) -> bool {
+    expected == actual
+        || ETag::block_hash(expected).is_some_and(|hash| ETag::block_hash(actual) == Some(hash))
+}
+
+#[test]
+fn test_multipart_file_verify_etag() {
+    use crate::{api::ApiRequest, client::HttpClient, util::test_server};
+
+    test_server::block_on(async {
+        // The etag of the tampered part is the etag of other data.
+        let (object_config, _) = test_server::serve(|_, received| {
+            let data = if received.uri.contains("tampered") {
+                b"other".as_slice()
+            } else {
+                &received.body
+            };
+            (
+                200,
+                vec![("ETag", format!("\"{}\"", ETag::from_bytes(data).etag))],
+                r#"{"PartNumber":1}"#.to_string(),
+            )
+        })
+        .await;
+        let upload = |upload_id: &str| {
+            MultipartFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .state(InitMultipartState {
+                    upload_id: upload_id.to_string(),
+                    blk_size: 4,
+                    bucket: "bucket".to_string(),
+                    key_name: "key".to_string(),
+                    mime_type: Some("text/plain".to_string()),
+                })
+                .buffer(Bytes::from_static(b"data"))
+                .part_index(1)
+                .verify_etag(true)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let part_state = upload("intact").await.unwrap();
+        assert_eq!(part_state.etag, ETag::from_bytes(b"data").etag);
+
+        let error = upload("tampered").await.unwrap_err();
+        assert!(matches!(
+            error,
+            UFileError::IntegrityMismatch {
+                ref key,
+                part_number: Some(1),
+                ref actual,
+                ..
+            } if key == "key" && *actual == ETag::from_bytes(b"other").etag
+        ));
+    });
+}
@@ -2,20 +2,22 @@
 
 use std::{collections::HashMap, fmt::Display};
 
-use anyhow::Error;
 use chrono::Local;
 use reqwest::{
     Method,
     header::{HeaderMap, HeaderName},
 };
 
+use crate::api::validator::are_headers_valid;
 use crate::{
     AuthorizationService,
     api::{
         ApiOperation, ObjectOptAuthParamBuilder,
-        object::{BaseResponse, FinishUploadResponse, InitMultipartState, MultipartUploadState},
+        object::{FinishUploadResponse, InitMultipartState, MultipartUploadState},
     },
     define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
 };
 
 define_operation_struct!(MultipartFinishOperation);
@@ -23,6 +25,7 @@ define_api_request!(
     MultipartFinishRequest,
     MultipartFinishOperationBuilder,
     FinishUploadResponse,
+    validate,
     {
         /// Required: Slice initial state
         pub state: InitMultipartState,
@@ -44,12 +47,30 @@ define_api_request!(
         #[builder(setter(into, strip_option), default)]
         pub metadata: Option<HashMap<String, String>>,
 
+        /// Optional: Local UFile ETag of the whole file, see `ETag::from_block_hashes`.
+        /// Fails with [`UFileError::IntegrityMismatch`] if ucloud.cn returns another etag.
+        #[builder(setter(into, strip_option), default)]
+        pub expected_etag: Option<String>,
+
         /// Optional: Security Token
         #[builder(setter(into, strip_option), default)]
         pub security_token: Option<String>,
     }
 );
 
+impl MultipartFinishRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            self.metadata.as_ref().and_then(Option::as_ref),
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
 /// UNCHANGED（默认值）:保持初始化时设置的用户自定义元数据不变。
 ///
 /// REPLACE：忽略初始化分片时设置的用户自定义元数据，直接采用Finish请求中指定的元数据。
@@ -73,7 +94,7 @@ impl Display for MetadataDirective {
 impl ApiOperation for MultipartFinishOperation {
     type Request = MultipartFinishRequest;
     type Response = FinishUploadResponse;
-    type Error = Error;
+    type Error = UFileError;
 
     async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
         let MultipartFinishRequest {
@@ -82,77 +103,76 @@ impl ApiOperation for MultipartFinishOperation {
             new_object,
             metadata_directive,
             metadata,
+            expected_etag,
             security_token,
             ..
         } = req;
         let mime_type = state
             .mime_type
             .clone()
-            .ok_or(Error::msg("mime type is unset."))?;
+            .ok_or_else(|| UFileError::InvalidRequest("mime type is unset.".to_string()))?;
         // let mime_type = "text/plain".to_string();
-        let date = Local::now().format("%Y%m%d%H%M%S").to_string();
-        let auth_object = ObjectOptAuthParamBuilder::default()
-            .method(Method::POST)
-            .bucket(state.bucket.as_str())
-            .key_name(state.key_name.as_str())
-            .content_type(mime_type.as_str())
-            .date(date.as_str())
-            .build()?;
-        let authorization =
-            AuthorizationService.authorization(auth_object, self.object_config.clone())?;
-        let mut headers = HeaderMap::new();
-        headers.insert("Content-Type", mime_type.parse().unwrap());
-        headers.insert("Accept", "*/*".parse().unwrap());
-        headers.insert("Date", date.parse().unwrap());
-        headers.insert("Authorization", authorization.parse().unwrap());
-        if let Some(ref security_token) = security_token
-            && !security_token.is_empty()
-        {
-            headers.insert("SecurityToken", security_token.parse().unwrap());
-        }
-        if let Some(ref directive) = metadata_directive {
-            headers.insert(
-                "X-Ufile-Metadata-Directive",
-                directive.to_string().parse().unwrap(),
-            );
-        }
-        // We must add metadata to headers if metadata is not empty.
-        let url = self
-            .object_config
-            .generate_final_host(state.bucket.as_str(), state.key_name.as_str());
-        let url = format!(
-            "{}?uploadId={}&newKey={}",
-            url,
-            state.upload_id,
-            new_object.as_ref().unwrap_or(&String::new())
-        );
-        // calc body.
-        part_states.sort_by(|a, b| a.part_number.cmp(&b.part_number));
-        let body_buffer = part_states
-            .iter()
-            .map(|item| item.etag.clone())
-            .collect::<Vec<_>>()
-            .join(",");
-        tracing::debug!("Finish multipart upload task body: {:?}", body_buffer);
-        headers.insert(
-            "Content-Length",
-            body_buffer.len().to_string().parse().unwrap(),
-        );
-        if let Some(ref metadata) = metadata {
-            for (k, v) in metadata {
-                headers.insert(
-                    format!("X-Ufile-Meta-{k}").parse::<HeaderName>().unwrap(),
-                    v.parse().unwrap(),
-                );
-            }
-        }
         let resp = self
             .client
-            .get_client()
-            .post(url)
-            .headers(headers)
-            .body(body_buffer)
-            .send()
+            .send_with_retry(RetryMode::ConnectOnly, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::POST)
+                    .bucket(state.bucket.as_str())
+                    .key_name(state.key_name.as_str())
+                    .content_type(mime_type.as_str())
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", mime_type.parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                if let Some(ref directive) = metadata_directive {
+                    headers.insert("X-Ufile-Metadata-Directive", directive.to_string().parse()?);
+                }
+                // We must add metadata to headers if metadata is not empty.
+                let url = self
+                    .object_config
+                    .generate_final_host(state.bucket.as_str(), state.key_name.as_str());
+                let url = format!(
+                    "{}?uploadId={}&newKey={}",
+                    url,
+                    state.upload_id,
+                    new_object.as_ref().unwrap_or(&String::new())
+                );
+                // calc body.
+                part_states.sort_by_key(|a| a.part_number);
+                let body_buffer = part_states
+                    .iter()
+                    .map(|item| item.etag.clone())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                tracing::debug!("Finish multipart upload task body: {:?}", body_buffer);
+                headers.insert("Content-Length", body_buffer.len().to_string().parse()?);
+                if let Some(ref metadata) = metadata {
+                    for (k, v) in metadata {
+                        headers.insert(
+                            format!("X-Ufile-Meta-{k}").parse::<HeaderName>()?,
+                            v.parse()?,
+                        );
+                    }
+                }
+                Ok(self
+                    .client
+                    .get_client()
+                    .post(url)
+                    .headers(headers)
+                    .body(body_buffer))
+            })
             .await?;
         tracing::info!("Finish multipart upload task: {:?}", resp);
         if resp.status().is_success() {
@@ -171,10 +191,27 @@ impl ApiOperation for MultipartFinishOperation {
                 response_body.etag = etag.to_string();
             }
             response_body.headers.extend(response_headers);
+            if let Some(expected) = expected_etag {
+                let actual = response_body.etag.trim_matches('"');
+                if expected != actual {
+                    tracing::error!(
+                        "Finish multipart upload of {} etag mismatch, expected: {}, actual: {}",
+                        state.key_name,
+                        expected,
+                        actual
+                    );
+                    return Err(UFileError::IntegrityMismatch {
+                        key: state.key_name,
+                        part_number: None,
+                        expected,
+                        actual: actual.to_string(),
+                    });
+                }
+            }
             return Ok(response_body);
         }
-        let base_response: BaseResponse = resp.json().await?;
-        tracing::error!("Finish multipart upload task failed: {:?}", base_response);
-        Err(Error::msg("Failed to finish multipart upload task."))
+        let error = UFileError::from_response(resp).await;
+        tracing::error!("Finish multipart upload task failed: {:?}", error);
+        Err(error)
     }
 }
@@ -1,14 +1,16 @@
-use anyhow::Error;
 use chrono::Local;
 use reqwest::{
     Method,
     header::{HeaderMap, HeaderName},
 };
 
+use crate::api::validator::are_headers_valid;
 use crate::{
     AuthorizationService,
     api::{ApiOperation, ObjectOptAuthParamBuilder, object::InitMultipartState},
     define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
 };
 
 define_operation_struct!(MultipartInitOperation);
@@ -17,6 +19,7 @@ define_api_request!(
     MultipartInitRequest,
     MultipartInitOperationBuilder,
     InitMultipartState,
+    validate,
     {
     /// Required: Key name
     #[builder(setter(into))]
@@ -44,11 +47,30 @@ define_api_request!(
     }
 );
 
+impl MultipartInitRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            self.mime_type.as_deref(),
+            self.metadata.as_ref().and_then(Option::as_ref),
+            [
+                (
+                    "X-Ufile-Storage-Class",
+                    self.storage_type.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
 #[async_trait::async_trait]
 impl ApiOperation for MultipartInitOperation {
     type Request = MultipartInitRequest;
     type Response = InitMultipartState;
-    type Error = Error;
+    type Error = UFileError;
 
     async fn execute(&self, request: Self::Request) -> Result<Self::Response, Self::Error> {
         let MultipartInitRequest {
@@ -60,54 +82,53 @@ impl ApiOperation for MultipartInitOperation {
             security_token,
             ..
         } = request;
-        let date = Local::now().format("&Y%m%d%H%M%S").to_string();
-        let auth_object = ObjectOptAuthParamBuilder::default()
-            .method(Method::POST)
-            .bucket(bucket_name.as_str())
-            .key_name(key_name.as_str())
-            .content_type(mime_type.as_str())
-            .date(date.as_str())
-            .build()?;
-        let authorization =
-            AuthorizationService.authorization(auth_object, self.object_config.clone())?;
-        let mut headers = HeaderMap::new();
-        headers.insert("Content-Type", mime_type.parse().unwrap());
-        headers.insert("Accept", "*/*".parse().unwrap());
-        headers.insert("Date", date.parse().unwrap());
-        headers.insert("Authorization", authorization.parse().unwrap());
-        if let Some(ref storage_type) = storage_type
-            && !storage_type.is_empty()
-        {
-            headers.insert("X-Ufile-Storage-Class", storage_type.parse().unwrap());
-        }
-        if let Some(ref security_token) = security_token
-            && !security_token.is_empty()
-        {
-            headers.insert("SecurityToken", security_token.parse().unwrap());
-        }
-        // We must add metadata to headers if metadata is not empty.
-        if let Some(ref metadata) = metadata
-            && !metadata.is_empty()
-        {
-            for (k, v) in metadata {
-                headers.insert(
-                    format!("X-Ufile-Meta-{k}").parse::<HeaderName>().unwrap(),
-                    v.parse().unwrap(),
-                );
-            }
-        }
-        let url = self
-            .object_config
-            .generate_final_host(bucket_name.as_str(), key_name.as_str());
-        let url = format!("{url}?uploads");
-        // do request to remote server to create initialization of the multipart upload task.
         let resp = self
             .client
-            .get_client()
-            .post(url)
-            .headers(headers)
-            .json("")
-            .send()
+            .send_with_retry(RetryMode::ConnectOnly, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::POST)
+                    .bucket(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .content_type(mime_type.as_str())
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", mime_type.parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref storage_type) = storage_type
+                    && !storage_type.is_empty()
+                {
+                    headers.insert("X-Ufile-Storage-Class", storage_type.parse()?);
+                }
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                // We must add metadata to headers if metadata is not empty.
+                if let Some(ref metadata) = metadata
+                    && !metadata.is_empty()
+                {
+                    for (k, v) in metadata {
+                        headers.insert(
+                            format!("X-Ufile-Meta-{k}").parse::<HeaderName>()?,
+                            v.parse()?,
+                        );
+                    }
+                }
+                let url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), key_name.as_str());
+                let url = format!("{url}?uploads");
+                // do request to remote server to create initialization of the multipart upload task.
+                Ok(self.client.get_client().post(url).headers(headers).json(""))
+            })
             .await?;
         ::tracing::debug!("Init multipart file response: {:?}", resp);
         if resp.status().is_success() {
@@ -115,6 +136,12 @@ impl ApiOperation for MultipartInitOperation {
             resp.mime_type.replace(mime_type.clone());
             return Ok(resp);
         }
-        Err(Error::msg("Failed to init multipart file"))
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Failed to init multipart file: {} with error: {:?}",
+            key_name,
+            error
+        );
+        Err(error)
     }
 }
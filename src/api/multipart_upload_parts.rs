@@ -0,0 +1,330 @@
+//! This module contains an api to upload every part of a local file for an initialized
+//! multipart upload task.
+//!
+//! Parts are read with positional reads and uploaded concurrently. At most `concurrency` parts are
+//! in flight and their buffers are recycled through an [`ObjectPool`], so the memory used by an
+//! upload is bounded by `concurrency * blk_size` no matter how large the file is.
+//! Completed parts can be recorded to an [`UploadJournal`] to resume the upload later.
+
+use std::{
+    fs::File,
+    io::{self, ErrorKind},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use bytes::BytesMut;
+use futures_util::{StreamExt, TryStreamExt, future, stream};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    api::{
+        ApiOperation, ApiRequest, MultipartFileRequestBuilder, ProgressTracker, UploadJournal,
+        object::{InitMultipartState, MultipartUploadState},
+    },
+    constant::DEFAULT_CONCURRENCY,
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    util::{fs::ChunkFile, pool::ObjectPool, split_ranges},
+};
+
+define_operation_struct!(MultipartUploadPartsOperation);
+
+define_api_request!(
+    MultipartUploadPartsRequest,
+    MultipartUploadPartsOperationBuilder,
+    Vec<MultipartUploadState>,
+    validate,
+    {
+        /// Required: Multipart upload initial state
+        pub state: InitMultipartState,
+
+        /// Required: Local file to upload
+        #[builder(setter(into))]
+        pub path: PathBuf,
+
+        /// Optional: Number of parts uploaded concurrently.
+        ///
+        /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
+        #[builder(setter(into, strip_option), default)]
+        pub concurrency: Option<usize>,
+
+        /// Optional: Max bytes of part buffers held in memory, lowers the concurrency to
+        /// `max_buffer_memory / blk_size` parts. At least one part is always in flight.
+        #[builder(setter(into, strip_option), default)]
+        pub max_buffer_memory: Option<u64>,
+
+        /// Optional: Compute and send `Content-MD5` of every part.
+        /// Default: false
+        #[builder(default)]
+        pub auto_content_md5: bool,
+
+        /// Optional: Verify the etag of every part against its SHA1, see
+        /// `MultipartFileRequest::verify_etag`.
+        /// Default: false
+        #[builder(default)]
+        pub verify_etag: bool,
+
+        /// Optional: Report the bytes sent to the tracker, parts recorded in the journal are
+        /// counted as transferred before.
+        #[builder(setter(into, strip_option), default)]
+        pub progress_tracker: Option<Arc<ProgressTracker>>,
+
+        /// Optional: Journal file of the upload. Parts recorded in the journal are skipped and
+        /// every uploaded part is recorded, the journal is created if it does not exist.
+        #[builder(setter(into, strip_option), default)]
+        pub journal: Option<PathBuf>,
+
+        ///  Optional: temporary `STS` token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl MultipartUploadPartsRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+impl MultipartUploadPartsRequest {
+    /// Number of parts in flight, and of part buffers held in memory.
+    fn parts_in_flight(&self) -> usize {
+        let mut concurrency = self.concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        if let Some(max_buffer_memory) = self.max_buffer_memory {
+            concurrency =
+                concurrency.min((max_buffer_memory / self.state.blk_size.max(1)).max(1) as usize);
+        }
+        concurrency
+    }
+}
+
+impl MultipartUploadPartsOperation {
+    /// Upload the parts, reading every part into a buffer taken from the pool.
+    async fn upload_parts(
+        &self,
+        request: MultipartUploadPartsRequest,
+        pool: &ObjectPool<BytesMut>,
+    ) -> Result<Vec<MultipartUploadState>, UFileError> {
+        let concurrency = request.parts_in_flight();
+        let MultipartUploadPartsRequest {
+            state,
+            path,
+            auto_content_md5,
+            verify_etag,
+            progress_tracker,
+            journal: journal_path,
+            security_token,
+            ..
+        } = request;
+        let file = File::open(&path)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(UFileError::InvalidRequest(format!(
+                "{path:?} is not a regular file"
+            )));
+        }
+        let mut journal = match journal_path {
+            Some(ref journal_path) => match UploadJournal::load(journal_path)? {
+                Some(journal) if journal.state.upload_id == state.upload_id => journal,
+                Some(journal) => {
+                    return Err(UFileError::InvalidRequest(format!(
+                        "Journal {:?} belongs to upload {}, not {}",
+                        journal_path, journal.state.upload_id, state.upload_id
+                    )));
+                }
+                None => {
+                    let journal = UploadJournal::new(state.clone(), path.as_path(), &metadata);
+                    journal.save(journal_path)?;
+                    journal
+                }
+            },
+            None => UploadJournal::new(state.clone(), path.as_path(), &metadata),
+        };
+        let ranges = split_ranges(metadata.len(), state.blk_size)?
+            .into_iter()
+            .enumerate()
+            .filter(|(part_index, _)| !journal.is_completed(*part_index))
+            .collect::<Vec<_>>();
+        tracing::debug!(
+            "Upload {} parts of {:?} with {} parts in flight, {} parts already uploaded",
+            ranges.len(),
+            path,
+            concurrency,
+            journal.parts.len()
+        );
+        if let Some(ref progress_tracker) = progress_tracker {
+            let remaining = ranges
+                .iter()
+                .map(|(_, range)| range.end() - range.start() + 1)
+                .sum::<u64>();
+            progress_tracker.resume(metadata.len() - remaining);
+        }
+        let mut part_states = journal.part_states();
+
+        let (file, state, path) = (&file, &state, &path);
+        let security_token = security_token.as_deref();
+        let progress_tracker = progress_tracker.as_ref();
+        stream::iter(ranges)
+            .map(|(part_index, range)| async move {
+                let mut buffer = pool.get();
+                let part_size = range.end() - range.start() + 1;
+                let bytes = ChunkFile::create_chunk_file_with_buffer(
+                    file,
+                    *range.start(),
+                    part_size,
+                    std::mem::take(&mut *buffer),
+                )?
+                .get_bytes();
+                if bytes.len() as u64 != part_size {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!(
+                            "File {:?} is truncated, expect {} bytes at {}, read {} bytes",
+                            path,
+                            part_size,
+                            range.start(),
+                            bytes.len()
+                        ),
+                    )
+                    .into());
+                }
+                let result = MultipartFileRequestBuilder::default()
+                    .object_config(self.object_config.clone())
+                    .client(self.client.clone())
+                    .state(state.clone())
+                    .buffer(bytes.clone())
+                    .part_index(part_index)
+                    .auto_content_md5(auto_content_md5)
+                    .verify_etag(verify_etag)
+                    .progress_tracker(progress_tracker.cloned())
+                    .security_token(security_token.map(ToString::to_string))
+                    .build()?
+                    .request()
+                    .await;
+                // Give the buffer back to the pool once the request released its bytes.
+                if let Ok(bytes) = bytes.try_into_mut() {
+                    *buffer = bytes;
+                }
+                result
+            })
+            .buffer_unordered(concurrency)
+            .try_for_each(|part_state| {
+                journal.record(part_state.part_number, part_state.etag.as_str());
+                part_states.push(part_state);
+                future::ready(match journal_path {
+                    Some(ref journal_path) => journal.save(journal_path),
+                    None => Ok(()),
+                })
+            })
+            .await?;
+        Ok(part_states)
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for MultipartUploadPartsOperation {
+    type Request = MultipartUploadPartsRequest;
+    type Response = Vec<MultipartUploadState>;
+    type Error = UFileError;
+
+    async fn execute(&self, request: Self::Request) -> Result<Self::Response, Self::Error> {
+        let blk_size = request.state.blk_size as usize;
+        let pool = ObjectPool::new(
+            move || BytesMut::with_capacity(blk_size),
+            request.parts_in_flight(),
+        );
+        self.upload_parts(request, &pool).await
+    }
+}
+
+#[test]
+fn test_multipart_upload_parts_bounded_buffers() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{client::HttpClient, util::test_server};
+
+    test_server::block_on(async {
+        let (object_config, _) = test_server::serve(|_, received| {
+            let part_number = received
+                .uri
+                .split("partNumber=")
+                .nth(1)
+                .unwrap()
+                .to_string();
+            (
+                200,
+                vec![("ETag", format!("\"etag-{part_number}\""))],
+                format!(r#"{{"PartNumber":{part_number}}}"#),
+            )
+        })
+        .await;
+        let dir = test_server::temp_dir("upload-parts");
+        let path = dir.join("file");
+        std::fs::write(&path, "0123456789abcdefghijklmnopqrstuvwxyz").unwrap();
+        let state = InitMultipartState {
+            upload_id: "upload".to_string(),
+            blk_size: 4,
+            bucket: "bucket".to_string(),
+            key_name: "key".to_string(),
+            mime_type: Some("text/plain".to_string()),
+        };
+        let request = |concurrency: usize, max_buffer_memory: Option<u64>| {
+            MultipartUploadPartsRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .state(state.clone())
+                .path(path.clone())
+                .concurrency(concurrency)
+                .max_buffer_memory(max_buffer_memory)
+                .build()
+                .unwrap()
+        };
+        // The buffer memory lowers the concurrency to two parts.
+        assert_eq!(request(3, None).parts_in_flight(), 3);
+        assert_eq!(request(8, Some(9)).parts_in_flight(), 2);
+
+        let operation = MultipartUploadPartsOperationBuilder::default()
+            .object_config(object_config.clone())
+            .client(HttpClient::builder().build().unwrap())
+            .build()
+            .unwrap();
+        // Count the buffers allocated for the 9 parts.
+        let allocated = Arc::new(AtomicUsize::new(0));
+        let counter = allocated.clone();
+        let pool = ObjectPool::new(
+            move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+                BytesMut::new()
+            },
+            3,
+        );
+        let mut part_states = operation
+            .upload_parts(request(3, None), &pool)
+            .await
+            .unwrap();
+        part_states.sort_by_key(|part_state| part_state.part_number);
+        assert_eq!(
+            part_states
+                .iter()
+                .map(|part_state| part_state.etag.as_str())
+                .collect::<Vec<_>>(),
+            (0..9).map(|i| format!("etag-{i}")).collect::<Vec<_>>()
+        );
+        let allocated = allocated.load(Ordering::Relaxed);
+        assert!((1..=3).contains(&allocated));
+
+        // The buffers are given back to the pool with the bytes of their last part, instead of
+        // being replaced by empty buffers because the bytes are still referenced.
+        let buffers = (0..allocated).map(|_| pool.get()).collect::<Vec<_>>();
+        assert!(buffers.iter().all(|buffer| buffer.capacity() >= 4));
+        std::fs::remove_dir_all(&dir).unwrap();
+    });
+}
@@ -1,14 +1,20 @@
 use std::{
     collections::HashMap,
-    fmt::{Display, Formatter},
+    fmt::{Debug, Display, Formatter},
+    path::PathBuf,
+    pin::Pin,
 };
 
-use anyhow::Error;
+use bytes::Bytes;
 use derive_builder::Builder;
-use reqwest::Method;
-use serde::{Deserialize, Serialize};
+use futures_util::Stream;
+use reqwest::{Method, header::HeaderMap};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::auth::{HmacSha1Signer, Signer};
+use crate::{
+    auth::{HmacSha1Signer, Signer},
+    error::UFileError,
+};
 
 /// U-cloud protocol
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
@@ -28,6 +34,7 @@ impl Display for UfileProtocol {
 }
 
 #[derive(Debug, Builder)]
+#[builder(build_fn(error = "UFileError"))]
 pub struct ObjectOptAuthParam {
     /// Required.
     /// Specify the http method.
@@ -52,11 +59,11 @@ pub struct ObjectOptAuthParam {
     /// Specify the date of the request.
     #[builder(setter(into, strip_option), default)]
     pub date: Option<String>,
-    /// Specify the source file to be copied.
+    /// Specify the source file to be copied as `/<bucket>/<key>`.
     ///
     /// Example:
     /// ```
-    /// let source = "ufile://bucket-name/file-name";
+    /// let source = "/bucket-name/file-name";
     /// ```
     #[builder(setter(into, strip_option), default)]
     pub x_ufile_copy_source: Option<String>,
@@ -155,17 +162,18 @@ impl ObjectConfig {
         bucket_name: &str,
         key_name: &str,
         expires: &str,
-    ) -> Result<String, Error> {
+    ) -> Result<String, UFileError> {
+        let invalid = |message: &str| Err(UFileError::InvalidRequest(message.to_string()));
         if bucket_name.is_empty() {
-            return Err(Error::msg("bucket must not be empty."));
+            return invalid("bucket must not be empty.");
         }
 
         if key_name.is_empty() {
-            return Err(Error::msg("key_name must not be empty."));
+            return invalid("key_name must not be empty.");
         }
 
-        if expires.parse::<u64>()? == 0 {
-            return Err(Error::msg("expires must not be zero."));
+        if expires.parse::<u64>().unwrap_or_default() == 0 {
+            return invalid("expires must be a positive number.");
         }
         let sign_data = format!(
             "{}\n{}\n{}\n{}\n/{}/{}",
@@ -263,3 +271,561 @@ pub struct HeadFileResponse {
     /// Last modified time.
     pub last_modified: Option<String>,
 }
+
+impl HeadFileResponse {
+    /// Read the file metadata from the lower cased response headers.
+    pub(crate) fn from_headers(headers: HashMap<String, String>) -> Result<Self, UFileError> {
+        let content_type = headers
+            .get("content-type")
+            .ok_or_else(|| UFileError::InvalidResponse("content-type header is missing".into()))?
+            .to_string();
+        let content_length = headers.get("content-length").ok_or_else(|| {
+            UFileError::InvalidResponse("content-length header is missing".into())
+        })?;
+        let content_length = content_length.parse::<u64>().map_err(|_| {
+            UFileError::InvalidResponse(format!("content-length [{content_length}] is invalid"))
+        })?;
+        Ok(HeadFileResponse {
+            content_type,
+            content_length,
+            etag: headers.get("etag").cloned(),
+            last_modified: headers.get("last-modified").cloned(),
+            headers: Some(headers),
+        })
+    }
+}
+
+/// This struct describe the response of list objects api request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListObjectsResponse {
+    #[serde(skip_deserializing)]
+    pub headers: HashMap<String, String>,
+    /// Bucket name
+    #[serde(rename = "Name", default)]
+    pub bucket: String,
+    /// Prefix of the listed keys.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Delimiter used to group keys into common prefixes.
+    #[serde(default)]
+    pub delimiter: Option<String>,
+    /// Max keys of one page.
+    #[serde(default, deserialize_with = "deserialize_lenient_u64")]
+    pub max_keys: Option<u64>,
+    /// Whether there are more keys after this page.
+    #[serde(default)]
+    pub is_truncated: bool,
+    /// Marker used to request the next page, empty if this is the last page.
+    #[serde(default)]
+    pub next_marker: Option<String>,
+    /// Objects of this page.
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub contents: Vec<ObjectSummary>,
+    /// Common prefixes of this page if a delimiter is set.
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub common_prefixes: Vec<CommonPrefix>,
+}
+
+/// This struct describe an object in the response of list objects api request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ObjectSummary {
+    /// Key name of the object.
+    pub key: String,
+    /// Content-length of the object.
+    #[serde(default, deserialize_with = "deserialize_lenient_size")]
+    pub size: u64,
+    /// ETag of the object.
+    #[serde(rename = "Etag", alias = "ETag", default)]
+    pub etag: Option<String>,
+    /// Last modified time in seconds since epoch.
+    #[serde(default, deserialize_with = "deserialize_lenient_u64")]
+    pub last_modified: Option<u64>,
+    /// Storage type: STANDARD | IA | ARCHIVE
+    #[serde(default)]
+    pub storage_class: Option<String>,
+    /// Mime type of the object.
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    /// User custom metadata.
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub user_meta: HashMap<String, String>,
+}
+
+/// This struct describe a common prefix in the response of list objects api request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CommonPrefix {
+    pub prefix: String,
+}
+
+/// This struct describe the response of list multipart uploads api request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListMultipartUploadsResponse {
+    #[serde(skip_deserializing)]
+    pub headers: HashMap<String, String>,
+    /// Prefix of the listed keys.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Marker used to request the next page, empty if this is the last page.
+    #[serde(default)]
+    pub next_marker: Option<String>,
+    /// In-progress multipart uploads of this page.
+    #[serde(
+        rename = "DataSet",
+        default,
+        deserialize_with = "deserialize_null_default"
+    )]
+    pub uploads: Vec<MultipartUploadSummary>,
+}
+
+/// This struct describe an in-progress multipart upload in the response of list multipart
+/// uploads api request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MultipartUploadSummary {
+    /// 上传 ID
+    pub upload_id: String,
+    /// Cloud object name
+    #[serde(rename = "FileName")]
+    pub key_name: String,
+    /// Bucket of the upload, set to the requested bucket if the server does not return it.
+    #[serde(default)]
+    pub bucket: String,
+    /// Time the upload started in seconds since epoch.
+    #[serde(default, deserialize_with = "deserialize_lenient_u64")]
+    pub start_time: Option<u64>,
+    /// Storage type: STANDARD | IA | ARCHIVE
+    #[serde(default)]
+    pub storage_class: Option<String>,
+}
+
+impl MultipartUploadSummary {
+    /// State of the multipart upload task used to abort or finish it.
+    ///
+    /// The mime type and the block size are not returned by the list api, pass the ones the
+    /// upload was initiated with.
+    pub fn to_init_state(&self, mime_type: Option<String>, blk_size: u64) -> InitMultipartState {
+        InitMultipartState {
+            upload_id: self.upload_id.clone(),
+            blk_size,
+            bucket: self.bucket.clone(),
+            key_name: self.key_name.clone(),
+            mime_type,
+        }
+    }
+}
+
+/// This struct describe the response of list parts api request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListPartsResponse {
+    #[serde(skip_deserializing)]
+    pub headers: HashMap<String, String>,
+    /// 上传 ID
+    #[serde(default)]
+    pub upload_id: Option<String>,
+    /// Whether there are more parts after this page.
+    #[serde(default)]
+    pub is_truncated: bool,
+    /// Part number marker used to request the next page.
+    #[serde(default, deserialize_with = "deserialize_lenient_u64")]
+    pub next_part_number_marker: Option<u64>,
+    /// Uploaded parts of this page.
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub parts: Vec<PartSummary>,
+}
+
+impl ListPartsResponse {
+    /// Part states of the uploaded parts used to finish the multipart upload task.
+    pub fn part_states(&self) -> Vec<MultipartUploadState> {
+        self.parts.iter().cloned().map(Into::into).collect()
+    }
+}
+
+/// This struct describe an uploaded part in the response of list parts api request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PartSummary {
+    /// Part number starting at 0.
+    pub part_number: usize,
+    /// Size of the part.
+    #[serde(default, deserialize_with = "deserialize_lenient_size")]
+    pub size: u64,
+    /// ETag of the part.
+    #[serde(rename = "ETag", alias = "Etag")]
+    pub etag: String,
+    /// Last modified time in seconds since epoch.
+    #[serde(default, deserialize_with = "deserialize_lenient_u64")]
+    pub last_modified: Option<u64>,
+}
+
+impl From<PartSummary> for MultipartUploadState {
+    fn from(part: PartSummary) -> Self {
+        MultipartUploadState {
+            headers: HashMap::new(),
+            part_number: part.part_number,
+            etag: part.etag.trim_matches('"').to_string(),
+        }
+    }
+}
+
+/// Deserialize `null` as the default value.
+fn deserialize_null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Deserialize a size which might be missing, string or number.
+fn deserialize_lenient_size<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(deserialize_lenient_u64(deserializer)?.unwrap_or_default())
+}
+
+/// Deserialize a number which ucloud.cn might send either as json number or as string.
+fn deserialize_lenient_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Lenient {
+        Number(u64),
+        Text(String),
+    }
+    match Option::<Lenient>::deserialize(deserializer)? {
+        Some(Lenient::Number(n)) => Ok(Some(n)),
+        Some(Lenient::Text(s)) if s.is_empty() => Ok(None),
+        Some(Lenient::Text(s)) => s.parse().map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// Outcome of a delete file api request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeleteOutcome {
+    /// The object existed and has been deleted.
+    Deleted,
+    /// The object does not exist, there was nothing to delete.
+    NotFound,
+}
+
+/// This struct describe the response of delete file api request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteFileResponse {
+    /// Http response headers
+    pub headers: HashMap<String, String>,
+    /// Request session id returned by ucloud.cn (`X-SessionId`).
+    pub session_id: Option<String>,
+    /// Whether the object was deleted or already gone.
+    pub outcome: DeleteOutcome,
+}
+
+/// This struct describe the response of copy file api request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CopyFileResponse {
+    /// Http response headers
+    pub headers: HashMap<String, String>,
+    /// Request session id returned by ucloud.cn (`X-SessionId`).
+    pub session_id: Option<String>,
+    /// ETag of the destination object.
+    pub etag: Option<String>,
+}
+
+/// This struct describe the response of rename file api request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameFileResponse {
+    /// Http response headers
+    pub headers: HashMap<String, String>,
+    /// Request session id returned by ucloud.cn (`X-SessionId`).
+    pub session_id: Option<String>,
+}
+
+/// Stream of the bytes of an object body.
+pub type ObjectBodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, UFileError>> + Send>>;
+
+/// This struct describe the response of get object api request.
+pub struct GetObjectResponse {
+    /// Http response headers
+    pub headers: HashMap<String, String>,
+    /// Content-Type of the file
+    pub content_type: Option<String>,
+    /// Content-length of the returned body.
+    pub content_length: Option<u64>,
+    /// ETag of the file.
+    pub etag: Option<String>,
+    /// Last modified time.
+    pub last_modified: Option<String>,
+    /// Range of the returned body, only set for partial content responses.
+    pub content_range: Option<ContentRange>,
+    /// Body of the file, read it to the end to release the connection.
+    pub body: ObjectBodyStream,
+}
+
+/// Outcome of a get object api request.
+#[derive(Debug)]
+pub enum GetObjectOutput {
+    /// 200: The whole object is returned.
+    Full(GetObjectResponse),
+    /// 206: The requested range of the object is returned.
+    PartialContent(GetObjectResponse),
+    /// 304: The object matches `If-None-Match` or is not modified since `If-Modified-Since`.
+    NotModified(HashMap<String, String>),
+    /// 412: The object does not match `If-Match` or is modified since `If-Unmodified-Since`.
+    PreconditionFailed(HashMap<String, String>),
+    /// 416: The requested range is outside of the object.
+    RangeNotSatisfiable(HashMap<String, String>),
+}
+
+impl GetObjectOutput {
+    /// Get the response carrying a body, returns `None` if no body is returned.
+    pub fn into_response(self) -> Option<GetObjectResponse> {
+        match self {
+            GetObjectOutput::Full(resp) | GetObjectOutput::PartialContent(resp) => Some(resp),
+            _ => None,
+        }
+    }
+
+    /// Http response headers
+    pub fn headers(&self) -> &HashMap<String, String> {
+        match self {
+            GetObjectOutput::Full(resp) | GetObjectOutput::PartialContent(resp) => &resp.headers,
+            GetObjectOutput::NotModified(headers)
+            | GetObjectOutput::PreconditionFailed(headers)
+            | GetObjectOutput::RangeNotSatisfiable(headers) => headers,
+        }
+    }
+}
+
+/// Byte range of an object to request with the `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// Bytes from `start` to `end`, both inclusive: `bytes=start-end`.
+    Inclusive { start: u64, end: u64 },
+    /// Bytes from `start` to the end of the object: `bytes=start-`.
+    From(u64),
+    /// The last `n` bytes of the object: `bytes=-n`.
+    Suffix(u64),
+}
+
+impl Display for ByteRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ByteRange::Inclusive { start, end } => write!(f, "bytes={start}-{end}"),
+            ByteRange::From(start) => write!(f, "bytes={start}-"),
+            ByteRange::Suffix(n) => write!(f, "bytes=-{n}"),
+        }
+    }
+}
+
+/// Range of the returned body parsed from the `Content-Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// First byte position, inclusive.
+    pub start: u64,
+    /// Last byte position, inclusive.
+    pub end: u64,
+    /// Total size of the object if known.
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parse `Content-Range` header value like `bytes 0-99/1000` or `bytes 0-99/*`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        let total = match total {
+            "*" => None,
+            total => Some(total.parse().ok()?),
+        };
+        Some(Self {
+            start: start.parse().ok()?,
+            end: end.parse().ok()?,
+            total,
+        })
+    }
+}
+
+impl Debug for GetObjectResponse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GetObjectResponse")
+            .field("headers", &self.headers)
+            .field("content_type", &self.content_type)
+            .field("content_length", &self.content_length)
+            .field("etag", &self.etag)
+            .field("last_modified", &self.last_modified)
+            .field("content_range", &self.content_range)
+            .finish_non_exhaustive()
+    }
+}
+
+/// This struct describe the result of download file api request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadFileResponse {
+    /// Path of the downloaded file.
+    pub path: PathBuf,
+    /// Size of the downloaded file.
+    pub size: u64,
+    /// ETag of the object.
+    pub etag: Option<String>,
+}
+
+/// This struct describe the result of upload file api request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadFileResponse {
+    /// Http response headers of the put file or finish multipart upload request.
+    pub headers: HashMap<String, String>,
+    /// ETag of the uploaded object.
+    pub etag: String,
+    /// Upload id if the file is uploaded with multipart upload.
+    pub upload_id: Option<String>,
+    /// Whether the object is created by upload hit without transferring data.
+    pub upload_hit: bool,
+}
+
+/// This struct describe the response of upload hit api request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadHitResponse {
+    /// Http response headers
+    pub headers: HashMap<String, String>,
+    /// Request session id returned by ucloud.cn (`X-SessionId`).
+    pub session_id: Option<String>,
+    /// Whether an object with the same hash and size exists and the key is created from it.
+    pub hit: bool,
+}
+
+/// Report of the abort stale uploads api request.
+#[derive(Debug, Default)]
+pub struct AbortStaleUploadsReport {
+    /// Whether the uploads were only reported and not aborted.
+    pub dry_run: bool,
+    /// Stale uploads which are aborted, or would be aborted in dry-run mode.
+    pub aborted: Vec<MultipartUploadSummary>,
+    /// Stale uploads which failed to be aborted.
+    pub failed: Vec<(MultipartUploadSummary, UFileError)>,
+}
+
+/// Collect response headers into a map with lowercase keys.
+pub(crate) fn response_headers(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(k, v)| {
+            (
+                k.as_str().to_lowercase(),
+                String::from_utf8_lossy(v.as_bytes()).to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_list_objects_response() {
+    let body = r#"{
+        "Name": "bucket",
+        "Prefix": "logs/",
+        "MaxKeys": "2",
+        "Delimiter": "/",
+        "IsTruncated": true,
+        "NextMarker": "logs/b.txt",
+        "Contents": [
+            {
+                "Key": "logs/a.txt",
+                "MimeType": "text/plain",
+                "LastModified": 1757756531,
+                "Etag": "AQAAAHmkb2Bb",
+                "Size": "11",
+                "StorageClass": "STANDARD",
+                "UserMeta": {"owner": "me"}
+            },
+            {"Key": "logs/b.txt", "Size": 0, "UserMeta": null}
+        ],
+        "CommonPrefixes": [{"Prefix": "logs/2025/"}]
+    }"#;
+    let resp: ListObjectsResponse = serde_json::from_str(body).unwrap();
+    assert_eq!(resp.max_keys, Some(2));
+    assert!(resp.is_truncated);
+    assert_eq!(resp.next_marker.as_deref(), Some("logs/b.txt"));
+    assert_eq!(resp.contents.len(), 2);
+    assert_eq!(resp.contents[0].size, 11);
+    assert_eq!(resp.contents[0].user_meta["owner"], "me");
+    assert_eq!(resp.contents[1].size, 0);
+    assert!(resp.contents[1].user_meta.is_empty());
+    assert_eq!(resp.common_prefixes[0].prefix, "logs/2025/");
+}
+
+#[test]
+fn test_byte_range() {
+    assert_eq!(
+        ByteRange::Inclusive { start: 0, end: 99 }.to_string(),
+        "bytes=0-99"
+    );
+    assert_eq!(ByteRange::From(100).to_string(), "bytes=100-");
+    assert_eq!(ByteRange::Suffix(10).to_string(), "bytes=-10");
+
+    assert_eq!(
+        ContentRange::parse("bytes 0-99/1000"),
+        Some(ContentRange {
+            start: 0,
+            end: 99,
+            total: Some(1000)
+        })
+    );
+    assert_eq!(ContentRange::parse("bytes 5-9/*").unwrap().total, None);
+    assert_eq!(ContentRange::parse("bytes */1000"), None);
+}
+
+#[test]
+fn test_list_multipart_uploads_response() {
+    let uploads: ListMultipartUploadsResponse = serde_json::from_str(
+        r#"{"RetCode":0,"Prefix":"backup/","NextMarker":"","DataSet":[
+            {"UploadId":"up1","FileName":"backup/a","StartTime":1700000000}]}"#,
+    )
+    .unwrap();
+    assert_eq!(uploads.uploads.len(), 1);
+    let state = uploads.uploads[0].to_init_state(Some("text/plain".to_string()), 8 << 20);
+    assert_eq!(state.upload_id, "up1");
+    assert_eq!(state.key_name, "backup/a");
+    assert_eq!(state.mime_type.as_deref(), Some("text/plain"));
+    assert_eq!(state.blk_size, 8 << 20);
+
+    let parts: ListPartsResponse = serde_json::from_str(
+        r#"{"RetCode":0,"UploadId":"up1","IsTruncated":false,"Parts":[
+            {"PartNumber":0,"Size":"4194304","ETag":"\"e0\"","LastModified":1700000000}]}"#,
+    )
+    .unwrap();
+    let part_states = parts.part_states();
+    assert_eq!(part_states[0].part_number, 0);
+    assert_eq!(part_states[0].etag, "e0");
+}
+
+#[test]
+fn test_head_file_response_from_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", "text/plain".parse().unwrap());
+    headers.insert("Content-Length", "11".parse().unwrap());
+    headers.insert(
+        "X-Ufile-Meta-Owner",
+        reqwest::header::HeaderValue::from_bytes("café".as_bytes()).unwrap(),
+    );
+    let resp = HeadFileResponse::from_headers(response_headers(&headers)).unwrap();
+    assert_eq!(resp.content_type, "text/plain");
+    assert_eq!(resp.content_length, 11);
+    assert_eq!(resp.headers.unwrap()["x-ufile-meta-owner"], "café");
+
+    headers.remove("Content-Length");
+    assert!(matches!(
+        HeadFileResponse::from_headers(response_headers(&headers)),
+        Err(UFileError::InvalidResponse(_))
+    ));
+    headers.insert("Content-Length", "eleven".parse().unwrap());
+    assert!(HeadFileResponse::from_headers(response_headers(&headers)).is_err());
+    headers.remove("Content-Type");
+    assert!(HeadFileResponse::from_headers(response_headers(&headers)).is_err());
+}
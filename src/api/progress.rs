@@ -0,0 +1,134 @@
+//! This module contains the progress reporting of uploads and downloads.
+//!
+//! A [`ProgressTracker`] counts the bytes transferred by all the requests of a transfer, e.g. the
+//! concurrent parts of a multipart upload, and reports every advance to its callback.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+/// Progress of a transfer reported to a [`ProgressCallback`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferProgress {
+    /// Bytes transferred so far, including the bytes of a resumed transfer.
+    pub transferred_bytes: u64,
+    /// Total bytes of the transfer if it is known.
+    pub total_bytes: Option<u64>,
+    /// Index of the part or chunk which advanced, `None` for single request transfers.
+    pub part_index: Option<usize>,
+    /// Average throughput of the bytes transferred since the tracker was created.
+    pub bytes_per_second: f64,
+}
+
+impl TransferProgress {
+    /// Transferred fraction between 0 and 1 if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total_bytes.map(|total_bytes| {
+            if total_bytes == 0 {
+                1.0
+            } else {
+                self.transferred_bytes as f64 / total_bytes as f64
+            }
+        })
+    }
+}
+
+/// Callback invoked whenever a transfer advanced. It is called from the tasks moving the data,
+/// so it should return quickly, e.g. by sending the progress to a channel.
+pub type ProgressCallback = Arc<dyn Fn(TransferProgress) + Send + Sync>;
+
+/// Progress shared by every request of a transfer.
+pub struct ProgressTracker {
+    callback: ProgressCallback,
+    total_bytes: Option<u64>,
+    transferred_bytes: AtomicU64,
+    /// Bytes transferred before the tracker was created, excluded from the throughput.
+    resumed_bytes: AtomicU64,
+    started_at: Instant,
+}
+
+impl ProgressTracker {
+    pub fn new(total_bytes: Option<u64>, callback: ProgressCallback) -> Self {
+        Self {
+            callback,
+            total_bytes,
+            transferred_bytes: AtomicU64::new(0),
+            resumed_bytes: AtomicU64::new(0),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Bytes transferred so far.
+    pub fn transferred_bytes(&self) -> u64 {
+        self.transferred_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Count the bytes of a resumed transfer which were transferred before, without reporting.
+    pub fn resume(&self, bytes: u64) {
+        self.resumed_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.transferred_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count `bytes` transferred by the part at `part_index` and report the progress.
+    pub fn advance(&self, bytes: u64, part_index: Option<usize>) {
+        let transferred_bytes = self.transferred_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.report(transferred_bytes, part_index);
+    }
+
+    /// Discard `bytes` of the part at `part_index` which failed and will be transferred again.
+    pub fn rewind(&self, bytes: u64, part_index: Option<usize>) {
+        let transferred_bytes = self.transferred_bytes.fetch_sub(bytes, Ordering::Relaxed) - bytes;
+        self.report(transferred_bytes, part_index);
+    }
+
+    fn report(&self, transferred_bytes: u64, part_index: Option<usize>) {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        let session_bytes =
+            transferred_bytes.saturating_sub(self.resumed_bytes.load(Ordering::Relaxed));
+        (self.callback)(TransferProgress {
+            transferred_bytes,
+            total_bytes: self.total_bytes,
+            part_index,
+            bytes_per_second: if elapsed > 0.0 {
+                session_bytes as f64 / elapsed
+            } else {
+                0.0
+            },
+        });
+    }
+}
+
+#[test]
+fn test_progress_tracker() {
+    use std::sync::Mutex;
+
+    let reports = Arc::new(Mutex::new(vec![]));
+    let tracker = ProgressTracker::new(Some(100), {
+        let reports = reports.clone();
+        Arc::new(move |progress| reports.lock().unwrap().push(progress))
+    });
+    tracker.resume(40);
+    tracker.advance(30, Some(1));
+    tracker.rewind(10, Some(1));
+    tracker.advance(40, Some(1));
+    assert_eq!(tracker.transferred_bytes(), 100);
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(
+        reports
+            .iter()
+            .map(|progress| progress.transferred_bytes)
+            .collect::<Vec<_>>(),
+        vec![70, 60, 100]
+    );
+    assert!(
+        reports
+            .iter()
+            .all(|progress| progress.part_index == Some(1))
+    );
+    assert_eq!(reports[2].fraction(), Some(1.0));
+}
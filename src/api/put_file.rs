@@ -1,15 +1,31 @@
 use reqwest::header::{HeaderMap, HeaderName};
-use std::str::FromStr;
+use std::{
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
-use crate::api::{object::PutObjectResultResponse, traits::ApiOperation};
+use crate::api::{
+    ByteStream, ProgressStream, ProgressTracker, object::PutObjectResultResponse,
+    traits::ApiOperation,
+};
 
-use anyhow::Error;
 use chrono::Local;
-use reqwest::Method;
+use reqwest::{Body, Method};
 
 use crate::api::object::ObjectOptAuthParamBuilder;
 
-use crate::{AuthorizationService, define_api_request, define_operation_struct};
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    client::base_response,
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+    util::{ETagHasher, digest::content_md5 as content_md5_of},
+};
 
 define_operation_struct!(PutFileOperation);
 
@@ -17,6 +33,7 @@ define_api_request!(
     PutFileRequest,
     PutFileOperationBuilder,
     PutObjectResultResponse,
+    validate,
     {
         /// Required: Bucket name
         #[builder(setter(into))]
@@ -33,13 +50,37 @@ define_api_request!(
         /// Required: File stream.
         pub stream: crate::api::ByteStream,
 
-        /// Optional: File length
-        pub content_length: usize,
+        /// Optional: File length, defaults to the length of the stream if it is known.
+        #[builder(setter(into, strip_option), default)]
+        pub content_length: Option<usize>,
 
         /// Optional: File MD5 checksum
         #[builder(setter(into, strip_option), default)]
         pub content_md5: ::std::option::Option<String>,
 
+        /// Optional: Compute and send `Content-MD5` of the data if `content_md5` is unset.
+        /// Default: false
+        #[builder(default)]
+        pub auto_content_md5: bool,
+
+        /// Optional: Verify the ETag returned by ucloud.cn against the UFile ETag of the data,
+        /// which is computed while the data is sent.
+        /// Fails with [`UFileError::IntegrityMismatch`] if they differ.
+        /// Default: false
+        #[builder(default)]
+        pub verify_etag: bool,
+
+        /// Optional: Report the bytes sent to the tracker, which may be shared with other
+        /// requests of the same transfer.
+        #[builder(setter(into, strip_option), default)]
+        pub progress_tracker: Option<Arc<ProgressTracker>>,
+
+        /// Optional: Size of the chunks the stream is sent in, streams of files and readers are
+        /// read into pooled buffers of this size.
+        /// Default: 512KB
+        #[builder(setter(into, strip_option), default)]
+        pub buffer_size: Option<usize>,
+
         /// Optional: User custom metadata
         #[builder(setter(strip_option), default)]
         pub metadatas: ::std::option::Option<::std::collections::HashMap<String, String>>,
@@ -58,11 +99,34 @@ define_api_request!(
     }
 );
 
+impl PutFileRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            self.mime_type.as_deref(),
+            self.metadatas.as_ref().and_then(Option::as_ref),
+            [
+                (
+                    "X-Ufile-Storage-Class",
+                    self.storage_type.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "Content-MD5",
+                    self.content_md5.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
 #[async_trait::async_trait]
 impl ApiOperation for PutFileOperation {
     type Request = PutFileRequest;
     type Response = PutObjectResultResponse;
-    type Error = Error;
+    type Error = UFileError;
 
     async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
         let PutFileRequest {
@@ -73,58 +137,47 @@ impl ApiOperation for PutFileOperation {
             metadatas,
             content_length,
             content_md5,
+            auto_content_md5,
+            verify_etag,
+            progress_tracker,
+            buffer_size,
             storage_type,
             iop_cmd,
             security_token,
             ..
         } = req;
-        let date = Local::now().format("%Y%m%d%H%M%S").to_string();
-        let content_type = mime_type.clone();
-        let mut auth_object_builder = ObjectOptAuthParamBuilder::default();
-        auth_object_builder
-            .method(Method::PUT)
-            .bucket(bucket_name.as_str())
-            .key_name(key_name.as_str())
-            .content_type(content_type.as_str())
-            .date(date.as_str());
-
         let mut headers = HeaderMap::new();
-        // add content md5 to auth object
-        if let Some(content_md5) = content_md5 {
-            auth_object_builder.content_md5(content_md5.as_str());
-            headers.insert("Content-MD5", content_md5.parse().unwrap());
+        let content_length = content_length
+            .or(stream.len().map(|len| len as usize))
+            .ok_or_else(|| UFileError::InvalidRequest("content length is unknown.".to_string()))?;
+        let content_md5 = match content_md5 {
+            None if auto_content_md5 => Some(content_md5_of(stream.bytes().ok_or_else(|| {
+                UFileError::InvalidRequest(
+                    "Content-MD5 can only be computed for in memory bytes.".to_string(),
+                )
+            })?)),
+            content_md5 => content_md5,
+        };
+        if let Some(ref content_md5) = content_md5 {
+            headers.insert("Content-MD5", content_md5.parse()?);
         }
-        let auth_object = auth_object_builder.build()?;
-        headers.insert(
-            "Content-Length",
-            content_length.to_string().parse().unwrap(),
-        );
-
-        let authorization =
-            AuthorizationService.authorization(auth_object, self.object_config.clone())?;
-        headers.insert("Authorization", authorization.parse().unwrap());
-        headers.insert("Content-Type", content_type.parse().unwrap());
-        headers.insert("Accept", "*/*".parse().unwrap());
-        headers.insert("Date", date.parse().unwrap());
+        headers.insert("Content-Length", content_length.to_string().parse()?);
+        headers.insert("Content-Type", mime_type.parse()?);
+        headers.insert("Accept", "*/*".parse()?);
 
         if let Some(storage_type) = storage_type {
-            headers.insert("X-Ufile-Storage-Class", storage_type.parse().unwrap());
+            headers.insert("X-Ufile-Storage-Class", storage_type.parse()?);
         }
 
         if let Some(security_token) = security_token {
-            headers.insert("SecurityToken", security_token.parse().unwrap());
+            headers.insert("SecurityToken", security_token.parse()?);
         }
 
-        if let Some(metadatas) = metadatas
-            && !metadatas.is_empty()
-        {
-            metadatas.iter().for_each(|(key, value)| {
+        if let Some(metadatas) = metadatas {
+            for (key, value) in metadatas.iter() {
                 let key = format!("X-Ufile-Meta-{key}");
-                headers.insert(
-                    HeaderName::from_str(key.as_str()).unwrap(),
-                    value.to_string().parse().unwrap(),
-                );
-            });
+                headers.insert(HeaderName::from_str(key.as_str())?, value.parse()?);
+            }
         }
 
         let mut url = self
@@ -134,16 +187,154 @@ impl ApiOperation for PutFileOperation {
             url = format!("{url}?{iop_cmd}");
         }
 
+        // In memory bytes and files can be sent again, other streams only once.
+        let mode = if stream.is_replayable() {
+            RetryMode::Idempotent
+        } else {
+            RetryMode::Never
+        };
+        let mut stream = Some(stream);
+        let mut etag_hasher = None;
+        // Bytes reported to the progress tracker by the previous attempt.
+        let mut sent: Option<Arc<AtomicUsize>> = None;
         let response = self
             .client
-            .send_file(url.as_str(), Method::PUT, headers, stream)
+            .send_with_retry(mode, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let mut auth_object_builder = ObjectOptAuthParamBuilder::default();
+                auth_object_builder
+                    .method(Method::PUT)
+                    .bucket(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .content_type(mime_type.as_str())
+                    .date(date.as_str());
+                if let Some(ref content_md5) = content_md5 {
+                    auth_object_builder.content_md5(content_md5.as_str());
+                }
+                let authorization = AuthorizationService
+                    .authorization(auth_object_builder.build()?, self.object_config.clone())?;
+                let mut headers = headers.clone();
+                headers.insert("Authorization", authorization.parse()?);
+                headers.insert("Date", date.parse()?);
+
+                let mut body =
+                    ProgressStream::from(match stream.as_ref().and_then(ByteStream::replay) {
+                        Some(replayed) => replayed?,
+                        None => stream.take().ok_or_else(|| {
+                            UFileError::InvalidRequest("stream can not be sent again.".to_string())
+                        })?,
+                    })
+                    .with_buffer_pool(self.client.buffer_pool().clone());
+                if let Some(buffer_size) = buffer_size {
+                    body = body.with_buffer_size(buffer_size);
+                }
+                // The data is hashed again by every attempt.
+                etag_hasher = verify_etag.then(|| Arc::new(Mutex::new(ETagHasher::new())));
+                if let Some(ref etag_hasher) = etag_hasher {
+                    body = body.with_etag_hasher(etag_hasher.clone());
+                }
+                if let Some(ref progress_tracker) = progress_tracker {
+                    if let Some(sent) = sent.take() {
+                        progress_tracker.rewind(sent.load(Ordering::Relaxed) as u64, None);
+                    }
+                    body = body.with_progress_tracker(progress_tracker.clone(), None);
+                    sent = Some(body.progress_counter());
+                }
+                Ok(self
+                    .client
+                    .get_client()
+                    .put(url.as_str())
+                    .headers(headers)
+                    .body(Body::wrap_stream(body)))
+            })
             .await?;
+        if !response.status().is_success() {
+            let error = UFileError::from_response(response).await;
+            tracing::error!("Failed to put file: {} with error: {:?}", key_name, error);
+            return Err(error);
+        }
+        let response = base_response(response).await?;
         tracing::debug!("put file response: {:?}", response);
         let mut put_file_response = PutObjectResultResponse::from(response);
         if let Some(e_tag) = put_file_response.resp.headers.get("etag") {
             put_file_response.etag = e_tag.to_string();
         }
+        if let Some(etag_hasher) = etag_hasher
+            && put_file_response.resp.ret_code == 0
+        {
+            let expected = etag_hasher.lock().unwrap().finalize().etag;
+            let actual = put_file_response.etag.trim_matches('"');
+            if expected != actual {
+                tracing::error!(
+                    "Put file {} etag mismatch, expected: {}, actual: {}",
+                    key_name,
+                    expected,
+                    actual
+                );
+                return Err(UFileError::IntegrityMismatch {
+                    key: key_name,
+                    part_number: None,
+                    expected,
+                    actual: actual.to_string(),
+                });
+            }
+        }
 
         Ok(put_file_response)
     }
 }
+
+#[test]
+fn test_put_file_verify_etag() {
+    use crate::{
+        api::ApiRequest,
+        client::HttpClient,
+        util::{ETag, test_server},
+    };
+
+    test_server::block_on(async {
+        // The etag of the tampered object is the etag of other data.
+        let (object_config, _) = test_server::serve(|_, received| {
+            let data = if received.uri.ends_with("tampered") {
+                b"other".as_slice()
+            } else {
+                &received.body
+            };
+            let etag = format!("\"{}\"", ETag::from_bytes(data).etag);
+            (200, vec![("ETag", etag)], String::new())
+        })
+        .await;
+        let put = |key_name: &str| {
+            PutFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name(key_name)
+                .mime_type("text/plain")
+                .stream(ByteStream::from_bytes("data".into()))
+                .verify_etag(true)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let response = put("intact").await.unwrap();
+        assert_eq!(
+            response.etag.trim_matches('"'),
+            ETag::from_bytes(b"data").etag
+        );
+
+        let error = put("tampered").await.unwrap_err();
+        assert!(matches!(
+            error,
+            UFileError::IntegrityMismatch {
+                ref key,
+                part_number: None,
+                ref expected,
+                ref actual,
+            } if key == "tampered"
+                && *expected == ETag::from_bytes(b"data").etag
+                && *actual == ETag::from_bytes(b"other").etag
+        ));
+    });
+}
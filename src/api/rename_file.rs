@@ -0,0 +1,190 @@
+//! This module contains an api to rename a file in place on the remote server ucloud.cn.
+
+use chrono::Local;
+use reqwest::{Method, StatusCode, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation,
+        object::{ObjectOptAuthParamBuilder, RenameFileResponse, response_headers},
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(RenameFileOperation);
+
+define_api_request!(
+    RenameFileRequest,
+    RenameFileOperationBuilder,
+    RenameFileResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Current key name of the file.
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Required: New key name of the file.
+        #[builder(setter(into))]
+        pub new_key_name: String,
+
+        /// Optional: Whether to overwrite the destination if it already exists.
+        /// Default: false
+        #[builder(default)]
+        pub force: bool,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl RenameFileRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for RenameFileOperation {
+    type Request = RenameFileRequest;
+    type Response = RenameFileResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let RenameFileRequest {
+            bucket_name,
+            key_name,
+            new_key_name,
+            force,
+            security_token,
+            ..
+        } = req;
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::ConnectOnly, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::PUT)
+                    .bucket(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .content_type("application/octet-stream")
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", "application/octet-stream".parse()?);
+                headers.insert("Content-Length", "0".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), key_name.as_str());
+                let url = format!(
+                    "{url}?newFileName={}&force={force}",
+                    urlencoding::encode(new_key_name.as_str())
+                );
+                Ok(self.client.get_client().put(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("Rename file response: {:?}", resp);
+        let status = resp.status();
+        if status.is_success() {
+            let headers = response_headers(resp.headers());
+            return Ok(RenameFileResponse {
+                session_id: headers.get("x-sessionid").cloned(),
+                headers,
+            });
+        }
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Failed to rename file: {} to {} with error: {:?}",
+            key_name,
+            new_key_name,
+            error
+        );
+        // ucloud.cn answers 409 Conflict if the new key exists and the rename is not forced.
+        if !force && status == StatusCode::CONFLICT {
+            return Err(UFileError::ObjectAlreadyExists(new_key_name));
+        }
+        Err(error)
+    }
+}
+
+#[test]
+fn test_rename_file_conflict() {
+    use crate::{
+        api::ApiRequest,
+        client::HttpClient,
+        util::test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        let (object_config, log) = serve(|_, received| {
+            if received.uri.starts_with("/exists") {
+                (409, vec![], String::new())
+            } else if received.uri.starts_with("/denied") {
+                (
+                    403,
+                    vec![],
+                    r#"{"RetCode":-148653,"ErrMsg":"key already exists, no permission"}"#
+                        .to_string(),
+                )
+            } else {
+                (200, vec![("X-SessionId", "s1".to_string())], String::new())
+            }
+        })
+        .await;
+        let rename = |key_name: &str, force: bool| {
+            RenameFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name(key_name)
+                .new_key_name("new/key")
+                .force(force)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let response = rename("old", false).await.unwrap();
+        assert_eq!(response.session_id.as_deref(), Some("s1"));
+        let received = log.lock().unwrap()[0].clone();
+        assert_eq!(received.method, "PUT");
+        assert_eq!(received.uri, "/old?newFileName=new%2Fkey&force=false");
+
+        let error = rename("exists", false).await.unwrap_err();
+        assert!(matches!(error, UFileError::ObjectAlreadyExists(ref key) if key == "new/key"));
+
+        // A forced rename reports the conflict as is.
+        let error = rename("exists", true).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+
+        // The message of other errors does not matter.
+        let error = rename("denied", false).await.unwrap_err();
+        assert!(error.is_access_denied());
+        assert_eq!(log.lock().unwrap().len(), 4);
+    });
+}
@@ -0,0 +1,111 @@
+//! This module contains an api to resume an interrupted multipart upload of a file from its
+//! [`UploadJournal`], e.g. after a crash or restart.
+//!
+//! Only the parts missing from the journal are uploaded before the upload task is finished.
+
+use std::path::PathBuf;
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    api::{
+        ApiOperation, ApiRequest, ProgressCallback, UploadFileRequestBuilder, UploadJournal,
+        object::UploadFileResponse,
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+};
+
+define_operation_struct!(ResumeUploadOperation);
+
+define_api_request!(
+    ResumeUploadRequest,
+    ResumeUploadOperationBuilder,
+    UploadFileResponse,
+    validate,
+    {
+        /// Required: Journal file written by the interrupted upload.
+        #[builder(setter(into))]
+        pub journal: PathBuf,
+
+        /// Optional: Number of parts uploaded concurrently.
+        ///
+        /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
+        #[builder(setter(into, strip_option), default)]
+        pub concurrency: Option<usize>,
+
+        /// Optional: Max bytes of part buffers held in memory, lowers the concurrency to fit.
+        #[builder(setter(into, strip_option), default)]
+        pub max_buffer_memory: Option<u64>,
+
+        /// Optional: Called with the progress of the upload, parts uploaded before are counted
+        /// as transferred.
+        #[builder(setter(into, strip_option), default)]
+        pub progress: Option<ProgressCallback>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl ResumeUploadRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for ResumeUploadOperation {
+    type Request = ResumeUploadRequest;
+    type Response = UploadFileResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let ResumeUploadRequest {
+            journal: journal_path,
+            concurrency,
+            max_buffer_memory,
+            progress,
+            security_token,
+            ..
+        } = req;
+        let journal = UploadJournal::load(&journal_path)?.ok_or_else(|| {
+            UFileError::InvalidRequest(format!("Journal {journal_path:?} does not exist"))
+        })?;
+        if !journal.is_source_unchanged(&std::fs::metadata(&journal.source)?) {
+            return Err(UFileError::InvalidRequest(format!(
+                "File {:?} changed since upload {} started",
+                journal.source, journal.state.upload_id
+            )));
+        }
+        let UploadJournal { state, source, .. } = journal;
+        Ok(UploadFileRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(state.bucket)
+            .key_name(state.key_name)
+            .source(source)
+            .mime_type(
+                state
+                    .mime_type
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+            )
+            // The journal is only used by multipart uploads.
+            .multipart_threshold(0u64)
+            .concurrency(concurrency)
+            .max_buffer_memory(max_buffer_memory)
+            .journal(journal_path)
+            .progress(progress)
+            .security_token(security_token)
+            .build()?
+            .request()
+            .await?)
+    }
+}
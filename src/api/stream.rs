@@ -1,101 +1,420 @@
 use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
     pin::Pin,
-    sync::{Arc, atomic::AtomicUsize},
-    task::Poll,
+    sync::{Arc, Mutex, PoisonError, atomic::AtomicUsize},
+    task::{Context, Poll},
 };
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures_util::{
-    AsyncRead, Stream,
-    io::{BufReader, Cursor},
+    AsyncRead, Stream, TryStreamExt,
+    io::{AllowStdIo, Cursor},
 };
-use pin_project_lite::pin_project;
 
-pin_project! {
-    pub struct ByteStream {
-        #[pin]
-        inner: Inner
-    }
+use crate::{
+    api::ProgressTracker,
+    constant::{DEFAULT_BUFFER_SIZE, DEFAULT_CONCURRENCY},
+    util::{
+        ETagHasher,
+        pool::{ObjectPool, PooledObject},
+    },
+};
+
+/// Body of an upload, either in memory bytes, a file, or any async reader or stream.
+pub struct ByteStream {
+    inner: Inner,
+    len: Option<u64>,
 }
 
-struct Inner(bytes::Bytes);
+enum Inner {
+    Bytes(Bytes),
+    /// A file, which is opened again at `path` to send it again.
+    File {
+        file: File,
+        path: PathBuf,
+    },
+    /// The reader is only accessed by value, the mutex makes the stream `Sync` without
+    /// requiring a `Sync` reader.
+    Reader(Mutex<ByteStreamReader>),
+}
+
+/// Reader of the bytes of a [`ByteStream`].
+pub type ByteStreamReader = Pin<Box<dyn AsyncRead + Send>>;
 
 impl ByteStream {
     pub fn from_bytes(bytes: Bytes) -> Self {
         Self {
-            inner: Inner(bytes),
+            len: Some(bytes.len() as u64),
+            inner: Inner::Bytes(bytes),
+        }
+    }
+
+    /// Stream the file at `path`, its length is taken from the file metadata.
+    ///
+    /// The file is read with blocking reads, so that no async runtime is required. Unlike other
+    /// readers the file can be sent again, e.g. when the request is retried.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            inner: Inner::File {
+                file,
+                path: path.to_path_buf(),
+            },
+            len: Some(len),
+        })
+    }
+
+    /// Stream any async reader, the length is unknown unless set with [`ByteStream::with_len`].
+    pub fn from_reader(reader: impl AsyncRead + Send + 'static) -> Self {
+        Self {
+            inner: Inner::Reader(Mutex::new(Box::pin(reader))),
+            len: None,
+        }
+    }
+
+    /// Stream any stream of bytes, the length is unknown unless set with [`ByteStream::with_len`].
+    pub fn from_stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Self::from_reader(Box::pin(stream.map_err(std::io::Error::other)).into_async_read())
+    }
+
+    /// Set the length of the stream, which is sent as `Content-Length`.
+    pub fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    /// Length of the stream if it is known.
+    pub fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Whether the stream is known to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == Some(0)
+    }
+
+    /// In memory bytes of the stream.
+    pub(crate) fn bytes(&self) -> Option<&Bytes> {
+        match self.inner {
+            Inner::Bytes(ref bytes) => Some(bytes),
+            Inner::File { .. } | Inner::Reader(_) => None,
         }
     }
+
+    /// Whether the stream can be sent again with [`ByteStream::replay`].
+    pub(crate) fn is_replayable(&self) -> bool {
+        !matches!(self.inner, Inner::Reader(_))
+    }
+
+    /// A new stream of the same bytes, `None` if the stream can only be read once.
+    ///
+    /// Files are opened again and fail if their length changed.
+    pub(crate) fn replay(&self) -> Option<io::Result<Self>> {
+        match self.inner {
+            Inner::Bytes(ref bytes) => Some(Ok(Self::from_bytes(bytes.clone()))),
+            Inner::File { ref path, .. } => Some(Self::from_path(path).and_then(|stream| {
+                if stream.len == self.len {
+                    Ok(stream)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("File {path:?} changed while it was sent"),
+                    ))
+                }
+            })),
+            Inner::Reader(_) => None,
+        }
+    }
+
+    /// Reader of the bytes of the stream.
+    pub fn into_reader(self) -> ByteStreamReader {
+        match self.inner {
+            Inner::Bytes(bytes) => Box::pin(Cursor::new(bytes)),
+            Inner::File { file, .. } => Box::pin(AllowStdIo::new(file)),
+            Inner::Reader(reader) => reader.into_inner().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+}
+
+impl From<Bytes> for ByteStream {
+    fn from(bytes: Bytes) -> Self {
+        Self::from_bytes(bytes)
+    }
 }
 
+/// Number of idle buffers kept by a pool of progress streams, two for every concurrent request.
+const POOLED_BUFFERS: usize = 2 * DEFAULT_CONCURRENCY;
+
 /// struct to wrap file reader with progress
+///
+/// In memory bytes are sent as slices without copying. Readers are read into pooled buffers of
+/// `buffer_size` bytes, a buffer returns to the pool once the bytes read into it are sent.
+/// Streams sent by a [`HttpClient`](crate::client::HttpClient) share the pool of the client.
 pub struct ProgressStream<T> {
-    reader: BufReader<T>,
+    source: Source<T>,
+    buffer_size: usize,
     progress: Arc<AtomicUsize>,
     size: usize,
+    etag_hasher: Option<Arc<Mutex<ETagHasher>>>,
+    progress_tracker: Option<(Arc<ProgressTracker>, Option<usize>)>,
 }
 
-impl<T: AsyncRead + Unpin> ProgressStream<T> {
-    pub fn new(reader: T, size: usize) -> Self {
+enum Source<T> {
+    Bytes(Bytes),
+    Reader {
+        reader: T,
+        pool: ObjectPool<BytesMut>,
+        /// Buffer of a read which is not ready yet.
+        buffer: Option<PooledObject<BytesMut>>,
+    },
+}
+
+/// Pooled buffer owning the bytes sent, it returns to the pool once they are dropped.
+struct PooledBuffer(PooledObject<BytesMut>);
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Pool of buffers of progress streams, buffers are resized to the buffer size of the stream.
+pub(crate) fn buffer_pool() -> ObjectPool<BytesMut> {
+    ObjectPool::new(BytesMut::new, POOLED_BUFFERS)
+}
+
+impl<T> ProgressStream<T> {
+    fn with_source(source: Source<T>, size: usize) -> Self {
         Self {
-            reader: BufReader::new(reader),
+            source,
+            buffer_size: DEFAULT_BUFFER_SIZE,
             progress: Arc::new(AtomicUsize::new(0)),
             size,
+            etag_hasher: None,
+            progress_tracker: None,
+        }
+    }
+
+    /// Stream the in memory bytes as slices of them.
+    pub fn from_bytes(bytes: Bytes) -> Self {
+        let size = bytes.len();
+        Self::with_source(Source::Bytes(bytes), size)
+    }
+}
+
+impl<T: AsyncRead + Unpin> ProgressStream<T> {
+    pub fn new(reader: T, size: usize) -> Self {
+        Self::with_source(
+            Source::Reader {
+                reader,
+                pool: buffer_pool(),
+                buffer: None,
+            },
+            size,
+        )
+    }
+
+    /// Set the max size of the chunks of the stream, which is the size of the buffers readers
+    /// are read into.
+    ///
+    /// Default: 512KB from `crate::constant::DEFAULT_BUFFER_SIZE`
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
+    /// Read into buffers of the pool, so that they are reused by other streams.
+    pub fn with_buffer_pool(mut self, buffer_pool: ObjectPool<BytesMut>) -> Self {
+        if let Source::Reader { pool, buffer, .. } = &mut self.source {
+            *pool = buffer_pool;
+            *buffer = None;
         }
+        self
+    }
+
+    /// Feed every byte read to the hasher, so that the ETag is known once the stream is
+    /// consumed without reading the data again.
+    pub fn with_etag_hasher(mut self, etag_hasher: Arc<Mutex<ETagHasher>>) -> Self {
+        self.etag_hasher = Some(etag_hasher);
+        self
+    }
+
+    /// Report every byte read to the tracker as progress of the part at `part_index`.
+    pub fn with_progress_tracker(
+        mut self,
+        progress_tracker: Arc<ProgressTracker>,
+        part_index: Option<usize>,
+    ) -> Self {
+        self.progress_tracker = Some((progress_tracker, part_index));
+        self
     }
 
     pub fn get_progress(&self) -> usize {
         self.progress.load(std::sync::atomic::Ordering::Relaxed)
     }
+
+    /// Counter of the bytes read, which stays reachable once the stream is moved into a body.
+    pub fn progress_counter(&self) -> Arc<AtomicUsize> {
+        self.progress.clone()
+    }
+
+    /// Next chunk of the source, `None` at the end of it.
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<Option<Bytes>>> {
+        match &mut self.source {
+            Source::Bytes(bytes) => {
+                let len = bytes.len().min(self.buffer_size);
+                Poll::Ready(Ok((len > 0).then(|| bytes.split_to(len))))
+            }
+            Source::Reader {
+                reader,
+                pool,
+                buffer,
+            } => {
+                let mut pooled = buffer.take().unwrap_or_else(|| pool.get());
+                if pooled.len() != self.buffer_size {
+                    pooled.resize(self.buffer_size, 0);
+                }
+                match Pin::new(reader).poll_read(cx, &mut pooled) {
+                    Poll::Ready(Ok(0)) => Poll::Ready(Ok(None)),
+                    Poll::Ready(Ok(n)) => {
+                        Poll::Ready(Ok(Some(Bytes::from_owner(PooledBuffer(pooled)).slice(..n))))
+                    }
+                    Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+                    Poll::Pending => {
+                        *buffer = Some(pooled);
+                        Poll::Pending
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<T: AsyncRead + Unpin> Stream for ProgressStream<T> {
     type Item = Result<bytes::Bytes, std::io::Error>;
 
-    fn poll_next(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Option<Self::Item>> {
-        // 8kb buffer
-        let mut buffer = [0u8; 8092];
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.get_mut();
-        let reader = Pin::new(&mut this.reader);
-        match reader.poll_read(cx, &mut buffer) {
-            std::task::Poll::Ready(Ok(n)) => {
-                if n == 0 {
-                    // we are at the end of file.
-                    return Poll::Ready(None);
-                }
-                let bytes = buffer[0..n].to_vec();
+        match this.poll_chunk(cx) {
+            Poll::Ready(Ok(Some(bytes))) => {
                 let num_bytes_read = bytes.len();
                 let prev = this
                     .progress
                     .fetch_add(num_bytes_read, std::sync::atomic::Ordering::Relaxed);
                 let current = prev + num_bytes_read;
+                if let Some(ref etag_hasher) = this.etag_hasher {
+                    etag_hasher.lock().unwrap().update(&bytes);
+                }
+                if let Some((ref progress_tracker, part_index)) = this.progress_tracker {
+                    progress_tracker.advance(num_bytes_read as u64, part_index);
+                }
                 // 计算并打印进度
-                let percent = (current as f64 / this.size as f64) * 100.0;
-                tracing::debug!(
-                    "Upload progress: {:.2}% ({} bytes/{} bytes)",
-                    percent,
-                    current,
-                    this.size
-                );
-                Poll::Ready(Some(Ok(Bytes::from_iter(bytes))))
+                if this.size > 0 {
+                    let percent = (current as f64 / this.size as f64) * 100.0;
+                    tracing::debug!(
+                        "Upload progress: {:.2}% ({} bytes/{} bytes)",
+                        percent,
+                        current,
+                        this.size
+                    );
+                } else {
+                    tracing::debug!("Upload progress: {} bytes", current);
+                }
+                Poll::Ready(Some(Ok(bytes)))
             }
-            std::task::Poll::Ready(Err(error)) => {
+            // we are at the end of file.
+            Poll::Ready(Ok(None)) => Poll::Ready(None),
+            Poll::Ready(Err(error)) => {
                 tracing::error!("Failed to read file, error: {:?}", error);
                 Poll::Ready(Some(Err(error)))
             }
-            std::task::Poll::Pending => Poll::Pending,
+            Poll::Pending => Poll::Pending,
         }
     }
 }
 
-impl From<ByteStream> for ProgressStream<Cursor<Bytes>> {
+impl From<ByteStream> for ProgressStream<ByteStreamReader> {
     fn from(stream: ByteStream) -> Self {
-        let bytes = stream.inner.0;
-        let size = bytes.len();
-        let cursor = Cursor::new(bytes);
-        Self::new(cursor, size)
+        let size = stream.len().unwrap_or_default() as usize;
+        match stream.inner {
+            Inner::Bytes(bytes) => Self::from_bytes(bytes),
+            Inner::File { file, .. } => Self::new(Box::pin(AllowStdIo::new(file)), size),
+            Inner::Reader(reader) => Self::new(
+                reader.into_inner().unwrap_or_else(PoisonError::into_inner),
+                size,
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_progress_stream_chunks() {
+    use futures_util::{FutureExt, StreamExt};
+
+    // The sources are always ready, the streams are consumed without an async runtime.
+    let data = Bytes::from((0..100u8).collect::<Vec<_>>());
+    let chunks = ProgressStream::<ByteStreamReader>::from_bytes(data.clone())
+        .with_buffer_size(30)
+        .collect::<Vec<_>>()
+        .now_or_never()
+        .unwrap();
+    assert_eq!(
+        chunks
+            .iter()
+            .map(|chunk| chunk.as_ref().unwrap().len())
+            .collect::<Vec<_>>(),
+        vec![30, 30, 30, 10]
+    );
+    // Slices share the memory of the bytes.
+    assert_eq!(chunks[1].as_ref().unwrap().as_ptr(), data[30..].as_ptr());
+
+    let stream = ProgressStream::new(Cursor::new(data.to_vec()), data.len()).with_buffer_size(30);
+    let read = stream
+        .map(|chunk| chunk.unwrap())
+        .collect::<Vec<_>>()
+        .now_or_never()
+        .unwrap()
+        .concat();
+    assert_eq!(read, data);
+}
+
+#[test]
+fn test_progress_stream_buffer_pool() {
+    use std::sync::atomic::Ordering;
+
+    use futures_util::{FutureExt, StreamExt};
+
+    // Count the buffers allocated by a pool shared by the streams.
+    let allocated = Arc::new(AtomicUsize::new(0));
+    let counter = allocated.clone();
+    let pool = ObjectPool::new(
+        move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            BytesMut::new()
+        },
+        POOLED_BUFFERS,
+    );
+    let data = (0..100u8).collect::<Vec<_>>();
+    for _ in 0..3 {
+        let read = ProgressStream::new(Cursor::new(data.clone()), data.len())
+            .with_buffer_size(30)
+            .with_buffer_pool(pool.clone())
+            .fold(Vec::new(), |mut read, chunk| async move {
+                read.extend_from_slice(&chunk.unwrap());
+                read
+            })
+            .now_or_never()
+            .unwrap();
+        assert_eq!(read, data);
     }
+    // Every chunk is dropped once it is consumed, so all streams reuse a single buffer.
+    assert_eq!(allocated.load(Ordering::Relaxed), 1);
 }
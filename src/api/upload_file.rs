@@ -0,0 +1,673 @@
+//! This module contains a high level api to upload a file to the remote server ucloud.cn.
+//!
+//! Sources smaller than the multipart threshold are uploaded with a single put file request,
+//! larger sources are uploaded with a multipart upload task whose parts are uploaded concurrently.
+//! Parts of files are read into pooled buffers, see [`MultipartUploadPartsOperation`].
+//! The multipart upload task is aborted if any part failed.
+
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use futures_util::{StreamExt, TryStreamExt, stream};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    api::{
+        ApiOperation, ApiRequest, ByteStream, MultipartAbortRequestBuilder,
+        MultipartFileRequestBuilder, MultipartFinishRequestBuilder, MultipartInitRequestBuilder,
+        MultipartUploadPartsRequestBuilder, ProgressCallback, ProgressTracker,
+        PutFileRequestBuilder, UploadHitRequestBuilder, UploadJournal,
+        object::{InitMultipartState, MultipartUploadState, UploadFileResponse},
+    },
+    constant::{
+        DEFAULT_CONCURRENCY, DEFAULT_MULTIPART_THRESHOLD, MAX_PUT_FILE_SIZE, MULTIPART_SIZE,
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    util::{ETag, digest::file_content_md5, fs::ChunkFile, split_ranges},
+};
+
+define_operation_struct!(UploadFileOperation);
+
+define_api_request!(
+    UploadFileRequest,
+    UploadFileOperationBuilder,
+    UploadFileResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Object key name
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Required: Source to upload.
+        #[builder(setter(into))]
+        pub source: UploadSource,
+
+        /// Optional: File MIME type
+        /// Default: application/octet-stream
+        #[builder(setter(into), default = "\"application/octet-stream\".to_string()")]
+        pub mime_type: String,
+
+        /// Optional: Sources of at least this size are uploaded with multipart upload.
+        ///
+        /// Default: 64MB from `crate::constant::DEFAULT_MULTIPART_THRESHOLD`, it can not exceed
+        /// the put file limit of 512MB.
+        #[builder(setter(into, strip_option), default)]
+        pub multipart_threshold: Option<u64>,
+
+        /// Optional: Number of parts uploaded concurrently.
+        ///
+        /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
+        #[builder(setter(into, strip_option), default)]
+        pub concurrency: Option<usize>,
+
+        /// Optional: Max bytes of part buffers held in memory when uploading a file with
+        /// multipart upload, lowers the concurrency to fit. Unlimited by default.
+        #[builder(setter(into, strip_option), default)]
+        pub max_buffer_memory: Option<u64>,
+
+        /// Optional: Size of the pooled buffers a file uploaded with a single put file request is
+        /// read into. Default: 512KB
+        #[builder(setter(into, strip_option), default)]
+        pub buffer_size: Option<usize>,
+
+        /// Optional: Journal file to make the multipart upload of a file resumable, see
+        /// [`UploadJournal`]. The upload continues from an existing journal of the same file and
+        /// object, and is not aborted on failure so that it can be resumed later.
+        /// The journal is removed once the upload is finished.
+        #[builder(setter(into, strip_option), default)]
+        pub journal: Option<PathBuf>,
+
+        /// Optional: Try an upload hit with the UFile ETag of the file before uploading it, which
+        /// creates the key without transferring data if ucloud.cn already stores the same file.
+        /// Worth it for large and frequently duplicated files, the source is read once more to
+        /// compute the ETag.
+        /// Default: false
+        #[builder(default)]
+        pub upload_hit: bool,
+
+        /// Optional: Verify the etag of the uploaded object, and of every part of a multipart
+        /// upload, against the data sent. Fails with
+        /// [`UFileError::IntegrityMismatch`](crate::error::UFileError::IntegrityMismatch) if they
+        /// differ.
+        /// Default: false
+        #[builder(default)]
+        pub verify_integrity: bool,
+
+        /// Optional: Compute and send `Content-MD5` of the put file or of every part. A file put
+        /// with a single request is read once more to compute it.
+        /// Default: false
+        #[builder(default)]
+        pub auto_content_md5: bool,
+
+        /// Optional: Called with the progress of the bytes sent by all requests of the upload.
+        #[builder(setter(into, strip_option), default)]
+        pub progress: Option<ProgressCallback>,
+
+        /// Optional: User custom metadata
+        #[builder(setter(into, strip_option), default)]
+        pub metadata: Option<::std::collections::HashMap<String, String>>,
+
+        /// Optional: Storage type: STANDARD | IA | ARCHIVE
+        #[builder(setter(into, strip_option), default)]
+        pub storage_type: Option<String>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl UploadFileRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            self.mime_type.as_deref(),
+            self.metadata.as_ref().and_then(Option::as_ref),
+            [
+                (
+                    "X-Ufile-Storage-Class",
+                    self.storage_type.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
+/// Source of an upload.
+#[derive(Debug, Clone)]
+pub enum UploadSource {
+    /// Regular file on the local file system.
+    Path(PathBuf),
+    /// In memory bytes.
+    Bytes(Bytes),
+}
+
+impl From<PathBuf> for UploadSource {
+    fn from(path: PathBuf) -> Self {
+        UploadSource::Path(path)
+    }
+}
+
+impl From<&std::path::Path> for UploadSource {
+    fn from(path: &std::path::Path) -> Self {
+        UploadSource::Path(path.to_path_buf())
+    }
+}
+
+impl From<Bytes> for UploadSource {
+    fn from(bytes: Bytes) -> Self {
+        UploadSource::Bytes(bytes)
+    }
+}
+
+/// Opened upload source which parts can be read from concurrently.
+enum OpenedSource {
+    File(File),
+    Bytes(Bytes),
+}
+
+impl OpenedSource {
+    fn open(source: &UploadSource) -> Result<(Self, u64), UFileError> {
+        match source {
+            UploadSource::Path(path) => {
+                let file = File::open(path)?;
+                let metadata = file.metadata()?;
+                if !metadata.is_file() {
+                    return Err(UFileError::InvalidRequest(format!(
+                        "{path:?} is not a regular file"
+                    )));
+                }
+                Ok((OpenedSource::File(file), metadata.len()))
+            }
+            UploadSource::Bytes(bytes) => {
+                let size = bytes.len() as u64;
+                Ok((OpenedSource::Bytes(bytes.clone()), size))
+            }
+        }
+    }
+
+    /// Read `size` bytes at `offset`.
+    fn read(&self, offset: u64, size: u64) -> Result<Bytes, UFileError> {
+        let bytes = match self {
+            OpenedSource::File(file) => {
+                ChunkFile::create_chunk_file(file, offset, size)?.get_bytes()
+            }
+            OpenedSource::Bytes(bytes) => bytes.slice(offset as usize..(offset + size) as usize),
+        };
+        if bytes.len() as u64 != size {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "Source is truncated, expect {} bytes at {}, read {} bytes",
+                    size,
+                    offset,
+                    bytes.len()
+                ),
+            )
+            .into());
+        }
+        Ok(bytes)
+    }
+}
+
+impl UploadFileOperation {
+    /// Try to create the key from a stored file with the same UFile ETag and size.
+    async fn upload_hit(
+        &self,
+        request: &UploadFileRequest,
+        size: u64,
+    ) -> Result<Option<UploadFileResponse>, UFileError> {
+        let etag = match request.source {
+            UploadSource::Path(ref path) => ETag::from_file(path, MULTIPART_SIZE)?.etag,
+            UploadSource::Bytes(ref bytes) => ETag::from_bytes(bytes).etag,
+        };
+        let response = UploadHitRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(request.bucket_name.as_str())
+            .key_name(request.key_name.as_str())
+            .hash(etag.as_str())
+            .file_size(size)
+            .mime_type(request.mime_type.as_str())
+            .security_token(request.security_token.clone())
+            .build()?
+            .request()
+            .await?;
+        tracing::debug!("Upload hit of {}: {}", request.key_name, response.hit);
+        Ok(response.hit.then_some(UploadFileResponse {
+            headers: response.headers,
+            etag,
+            upload_id: None,
+            upload_hit: true,
+        }))
+    }
+
+    /// Upload the whole source with a single put file request.
+    async fn put_file(
+        &self,
+        request: &UploadFileRequest,
+        source: OpenedSource,
+        size: u64,
+        progress_tracker: Option<Arc<ProgressTracker>>,
+    ) -> Result<UploadFileResponse, UFileError> {
+        let (stream, content_md5) = match request.source {
+            // Files are streamed from disk and opened again if the request is retried,
+            // `Content-MD5` is computed by reading the file once more up front.
+            UploadSource::Path(ref path) => (
+                ByteStream::from_path(path)?,
+                request
+                    .auto_content_md5
+                    .then(|| file_content_md5(path))
+                    .transpose()?,
+            ),
+            UploadSource::Bytes(_) => (ByteStream::from_bytes(source.read(0, size)?), None),
+        };
+        let response = PutFileRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(request.bucket_name.as_str())
+            .key_name(request.key_name.as_str())
+            .mime_type(request.mime_type.as_str())
+            .stream(stream)
+            .content_length(size as usize)
+            .content_md5(content_md5)
+            .auto_content_md5(request.auto_content_md5)
+            .verify_etag(request.verify_integrity)
+            .progress_tracker(progress_tracker)
+            .buffer_size(request.buffer_size)
+            .metadatas(request.metadata.clone())
+            .storage_type(request.storage_type.clone())
+            .security_token(request.security_token.clone())
+            .build()?
+            .request()
+            .await?;
+        Ok(UploadFileResponse {
+            headers: response.resp.headers,
+            etag: response.etag,
+            upload_id: None,
+            upload_hit: false,
+        })
+    }
+
+    /// Upload every part of the source concurrently.
+    async fn upload_parts(
+        &self,
+        state: &InitMultipartState,
+        request: &UploadFileRequest,
+        size: u64,
+        progress_tracker: Option<&Arc<ProgressTracker>>,
+    ) -> Result<Vec<MultipartUploadState>, UFileError> {
+        let concurrency = request.concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        let bytes = match &request.source {
+            UploadSource::Path(path) => {
+                return MultipartUploadPartsRequestBuilder::default()
+                    .object_config(self.object_config.clone())
+                    .client(self.client.clone())
+                    .state(state.clone())
+                    .path(path.as_path())
+                    .concurrency(concurrency)
+                    .max_buffer_memory(request.max_buffer_memory)
+                    .journal(request.journal.clone())
+                    .auto_content_md5(request.auto_content_md5)
+                    .verify_etag(request.verify_integrity)
+                    .progress_tracker(progress_tracker.cloned())
+                    .security_token(request.security_token.clone())
+                    .build()?
+                    .request()
+                    .await;
+            }
+            UploadSource::Bytes(bytes) => bytes,
+        };
+        let ranges = split_ranges(size, state.blk_size)?;
+        stream::iter(ranges.into_iter().enumerate())
+            .map(|(part_index, range)| async move {
+                MultipartFileRequestBuilder::default()
+                    .object_config(self.object_config.clone())
+                    .client(self.client.clone())
+                    .state(state.clone())
+                    .buffer(bytes.slice(*range.start() as usize..=*range.end() as usize))
+                    .part_index(part_index)
+                    .auto_content_md5(request.auto_content_md5)
+                    .verify_etag(request.verify_integrity)
+                    .progress_tracker(progress_tracker.cloned())
+                    .security_token(request.security_token.clone())
+                    .build()?
+                    .request()
+                    .await
+            })
+            .buffer_unordered(concurrency)
+            .try_collect()
+            .await
+    }
+
+    /// Abort the multipart upload task, failures are logged only.
+    async fn abort(&self, state: InitMultipartState, security_token: Option<String>) {
+        let result = match MultipartAbortRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .state(state)
+            .security_token(security_token)
+            .build()
+        {
+            Ok(request) => request.request().await,
+            Err(e) => Err(e),
+        };
+        if let Err(abort_error) = result {
+            tracing::error!("Failed to abort multipart upload: {:?}", abort_error);
+        }
+    }
+
+    /// Continue the multipart upload task recorded in the journal if it was started for the
+    /// same file and object, otherwise discard the journal.
+    async fn resumable_state(
+        &self,
+        request: &UploadFileRequest,
+    ) -> Result<Option<InitMultipartState>, UFileError> {
+        // Journals are only supported when uploading a file.
+        let (Some(journal_path), UploadSource::Path(path)) = (&request.journal, &request.source)
+        else {
+            return Ok(None);
+        };
+        let metadata = fs::metadata(path)?;
+        match UploadJournal::load(journal_path)? {
+            Some(journal)
+                if journal.is_resumable_for(&request.bucket_name, &request.key_name, &metadata) =>
+            {
+                tracing::info!(
+                    "Resume upload {} of {}, uploaded parts: {}",
+                    journal.state.upload_id,
+                    journal.state.key_name,
+                    journal.parts.len()
+                );
+                Ok(Some(journal.state))
+            }
+            Some(journal) => {
+                tracing::info!(
+                    "Discard outdated journal {:?}, abort upload {}",
+                    journal_path,
+                    journal.state.upload_id
+                );
+                self.abort(journal.state, request.security_token.clone())
+                    .await;
+                fs::remove_file(journal_path)?;
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Upload the source with a multipart upload task.
+    ///
+    /// The task is aborted if it failed, unless it is recorded in a journal to be resumed.
+    async fn multipart_upload(
+        &self,
+        request: &UploadFileRequest,
+        size: u64,
+        progress_tracker: Option<Arc<ProgressTracker>>,
+    ) -> Result<UploadFileResponse, UFileError> {
+        let UploadFileRequest {
+            bucket_name,
+            key_name,
+            mime_type,
+            metadata,
+            storage_type,
+            journal,
+            security_token,
+            ..
+        } = request;
+        let state = match self.resumable_state(request).await? {
+            Some(state) => state,
+            None => {
+                MultipartInitRequestBuilder::default()
+                    .object_config(self.object_config.clone())
+                    .client(self.client.clone())
+                    .bucket_name(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .mime_type(mime_type.as_str())
+                    .metadata(metadata.clone())
+                    .storage_type(storage_type.clone())
+                    .security_token(security_token.clone())
+                    .build()?
+                    .request()
+                    .await?
+            }
+        };
+
+        let result = match self
+            .upload_parts(&state, request, size, progress_tracker.as_ref())
+            .await
+        {
+            Ok(part_states) => match MultipartFinishRequestBuilder::default()
+                .object_config(self.object_config.clone())
+                .client(self.client.clone())
+                .state(state.clone())
+                .expected_etag(if request.verify_integrity {
+                    expected_etag(&state, &part_states)
+                } else {
+                    None
+                })
+                .part_states(part_states)
+                .security_token(security_token.clone())
+                .build()
+            {
+                Ok(request) => request.request().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(response) => {
+                if let Some(journal) = journal
+                    && journal.try_exists()?
+                {
+                    fs::remove_file(journal)?;
+                }
+                Ok(UploadFileResponse {
+                    headers: response.headers,
+                    etag: response.etag,
+                    upload_id: Some(state.upload_id),
+                    upload_hit: false,
+                })
+            }
+            Err(e) if journal.is_some() => {
+                tracing::error!(
+                    "Multipart upload of {} failed, keep upload {} to resume it with journal {:?}: {:?}",
+                    state.key_name,
+                    state.upload_id,
+                    journal,
+                    e
+                );
+                Err(e)
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Multipart upload of {} failed, abort upload {}: {:?}",
+                    state.key_name,
+                    state.upload_id,
+                    e
+                );
+                self.abort(state, security_token.clone()).await;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// UFile ETag of the whole file composed from the etags of the uploaded parts, which are the
+/// SHA1 of their block if the block size is 4MB.
+pub(crate) fn expected_etag(
+    state: &InitMultipartState,
+    part_states: &[MultipartUploadState],
+) -> Option<String> {
+    if state.blk_size != MULTIPART_SIZE as u64 {
+        return None;
+    }
+    let mut part_states = part_states.iter().collect::<Vec<_>>();
+    part_states.sort_by_key(|part_state| part_state.part_number);
+    let block_hashes = part_states
+        .iter()
+        .map(|part_state| ETag::block_hash(&part_state.etag))
+        .collect::<Option<Vec<_>>>()?;
+    Some(ETag::from_block_hashes(&block_hashes).etag)
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for UploadFileOperation {
+    type Request = UploadFileRequest;
+    type Response = UploadFileResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        if req.journal.is_some() && matches!(req.source, UploadSource::Bytes(_)) {
+            return Err(UFileError::InvalidRequest(
+                "Journal is only supported when uploading a file".to_string(),
+            ));
+        }
+        let (source, size) = OpenedSource::open(&req.source)?;
+        if req.upload_hit
+            && let Some(response) = self.upload_hit(&req, size).await?
+        {
+            return Ok(response);
+        }
+        let threshold = req
+            .multipart_threshold
+            .unwrap_or(DEFAULT_MULTIPART_THRESHOLD)
+            .min(MAX_PUT_FILE_SIZE);
+        let progress_tracker = req
+            .progress
+            .clone()
+            .map(|callback| Arc::new(ProgressTracker::new(Some(size), callback)));
+        // Empty sources have no part to upload with multipart upload.
+        if size < threshold || size == 0 {
+            self.put_file(&req, source, size, progress_tracker).await
+        } else {
+            // Parts are read from the source by the multipart upload itself.
+            drop(source);
+            self.multipart_upload(&req, size, progress_tracker).await
+        }
+    }
+}
+
+#[test]
+fn test_upload_file_threshold() {
+    use crate::{client::HttpClient, util::test_server};
+
+    test_server::block_on(async {
+        let (object_config, log) = test_server::serve(|_, received| {
+            if received.uri.ends_with("?uploads") {
+                let body = r#"{"UploadId":"upload","BlkSize":4,"Bucket":"bucket","Key":"key"}"#;
+                (200, vec![], body.to_string())
+            } else if let Some((_, part_number)) = received.uri.split_once("partNumber=") {
+                (
+                    200,
+                    vec![("ETag", format!("\"etag-{part_number}\""))],
+                    format!(r#"{{"PartNumber":{part_number}}}"#),
+                )
+            } else if received.uri.contains("uploadId=") {
+                let body = r#"{"Bucket":"bucket","Key":"key","FileSize":10}"#;
+                (
+                    200,
+                    vec![("ETag", "\"multipart\"".to_string())],
+                    body.to_string(),
+                )
+            } else {
+                (200, vec![("ETag", "\"put\"".to_string())], String::new())
+            }
+        })
+        .await;
+        let dir = test_server::temp_dir("upload-file");
+        let path = dir.join("file");
+        let upload = |multipart_threshold: u64| {
+            UploadFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name("key")
+                .source(path.as_path())
+                .multipart_threshold(multipart_threshold)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let requests = || {
+            std::mem::take(&mut *log.lock().unwrap())
+                .into_iter()
+                .map(|received| (received.method, received.body))
+                .collect::<Vec<_>>()
+        };
+
+        // Files below the threshold are uploaded with a single put file request.
+        std::fs::write(&path, "0123456789").unwrap();
+        let response = upload(11).await.unwrap();
+        assert_eq!(response.upload_id, None);
+        assert_eq!(response.etag.trim_matches('"'), "put");
+        assert_eq!(
+            requests(),
+            vec![("PUT".to_string(), b"0123456789".to_vec())]
+        );
+
+        // Init, three parts of at most 4 bytes and finish.
+        let response = upload(10).await.unwrap();
+        assert_eq!(response.upload_id.as_deref(), Some("upload"));
+        assert_eq!(response.etag.trim_matches('"'), "multipart");
+        let mut multipart = requests();
+        assert_eq!(multipart.len(), 5);
+        assert_eq!(multipart.remove(0).0, "POST");
+        assert_eq!(multipart.pop().unwrap().0, "POST");
+        let mut parts = multipart
+            .into_iter()
+            .map(|(method, body)| {
+                assert_eq!(method, "PUT");
+                body
+            })
+            .collect::<Vec<_>>();
+        parts.sort();
+        assert_eq!(
+            parts,
+            vec![b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()]
+        );
+
+        // `Content-MD5` of a file is computed without reading it into memory.
+        let response = UploadFileRequestBuilder::default()
+            .object_config(object_config.clone())
+            .client(HttpClient::builder().build().unwrap())
+            .bucket_name("bucket")
+            .key_name("key")
+            .source(path.as_path())
+            .auto_content_md5(true)
+            .build()
+            .unwrap()
+            .request()
+            .await
+            .unwrap();
+        assert_eq!(response.upload_id, None);
+        let received = std::mem::take(&mut *log.lock().unwrap());
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            received[0].headers["content-md5"],
+            crate::util::digest::content_md5(b"0123456789")
+        );
+        assert_eq!(received[0].body, b"0123456789");
+
+        // Empty files are put even if the threshold is 0.
+        std::fs::write(&path, "").unwrap();
+        let response = upload(0).await.unwrap();
+        assert_eq!(response.upload_id, None);
+        assert_eq!(requests(), vec![("PUT".to_string(), Vec::new())]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    });
+}
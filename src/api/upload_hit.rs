@@ -0,0 +1,223 @@
+//! This module contains an api to upload a file instantly ("upload hit") on the remote server
+//! ucloud.cn.
+//!
+//! ucloud.cn is asked whether an object with the same UFile ETag and size already exists. If so,
+//! the key is created from it without transferring any data.
+
+use chrono::Local;
+use reqwest::{Method, StatusCode, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation,
+        object::{ObjectOptAuthParamBuilder, UploadHitResponse, response_headers},
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(UploadHitOperation);
+
+define_api_request!(
+    UploadHitRequest,
+    UploadHitOperationBuilder,
+    UploadHitResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Object key name
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Required: UFile ETag of the file, see `ETag::from_file`.
+        #[builder(setter(into))]
+        pub hash: String,
+
+        /// Required: Size of the file.
+        pub file_size: u64,
+
+        /// Optional: File MIME type
+        /// Default: application/octet-stream
+        #[builder(setter(into), default = "\"application/octet-stream\".to_string()")]
+        pub mime_type: String,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl UploadHitRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            self.mime_type.as_deref(),
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for UploadHitOperation {
+    type Request = UploadHitRequest;
+    type Response = UploadHitResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let UploadHitRequest {
+            bucket_name,
+            key_name,
+            hash,
+            file_size,
+            mime_type,
+            security_token,
+            ..
+        } = req;
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::POST)
+                    .bucket(bucket_name.as_str())
+                    .key_name(key_name.as_str())
+                    .content_type(mime_type.as_str())
+                    .date(date.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", mime_type.parse()?);
+                headers.insert("Content-Length", "0".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let url = self
+                    .object_config
+                    .generate_final_host(bucket_name.as_str(), "");
+                let url = format!(
+                    "{url}uploadhit?Hash={}&FileName={}&FileSize={}",
+                    urlencoding::encode(&hash),
+                    urlencoding::encode(&key_name),
+                    file_size
+                );
+                Ok(self.client.get_client().post(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("Upload hit response: {:?}", resp);
+        let status = resp.status();
+        let headers = response_headers(resp.headers());
+        let hit = if status.is_success() {
+            true
+        } else if status == StatusCode::NOT_FOUND {
+            // No object with the same hash exists, the file has to be uploaded.
+            false
+        } else {
+            let error = UFileError::from_response(resp).await;
+            tracing::error!("Failed to upload hit: {} with error: {:?}", key_name, error);
+            return Err(error);
+        };
+        Ok(UploadHitResponse {
+            session_id: headers.get("x-sessionid").cloned(),
+            headers,
+            hit,
+        })
+    }
+}
+
+#[test]
+fn test_upload_hit() {
+    use crate::{
+        api::{ApiRequest, UploadFileRequestBuilder},
+        client::HttpClient,
+        util::{
+            ETag,
+            test_server::{block_on, serve},
+        },
+    };
+
+    block_on(async {
+        // Only the object `hit` is already stored.
+        let (object_config, log) = serve(|_, received| {
+            if received.method == "PUT" {
+                (200, vec![("ETag", "\"put\"".to_string())], String::new())
+            } else if received.uri.contains("FileName=hit") {
+                (200, vec![("X-SessionId", "s1".to_string())], String::new())
+            } else {
+                let body = r#"{"RetCode":-1,"ErrMsg":"file not exist"}"#;
+                (404, vec![], body.to_string())
+            }
+        })
+        .await;
+        let etag = ETag::from_bytes(b"data").etag;
+        let upload_hit = |key_name: &str| {
+            UploadHitRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name(key_name)
+                .hash(etag.as_str())
+                .file_size(4u64)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let response = upload_hit("hit").await.unwrap();
+        assert!(response.hit);
+        assert_eq!(response.session_id.as_deref(), Some("s1"));
+        let received = log.lock().unwrap().remove(0);
+        assert_eq!(received.method, "POST");
+        assert_eq!(
+            received.uri,
+            format!(
+                "/uploadhit?Hash={}&FileName=hit&FileSize=4",
+                urlencoding::encode(&etag)
+            )
+        );
+        assert!(!upload_hit("miss").await.unwrap().hit);
+        log.lock().unwrap().clear();
+
+        // The file is only transferred if the upload hit missed.
+        let upload_file = |key_name: &str| {
+            UploadFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name(key_name)
+                .source(bytes::Bytes::from_static(b"data"))
+                .upload_hit(true)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let methods = || {
+            std::mem::take(&mut *log.lock().unwrap())
+                .into_iter()
+                .map(|received| received.method)
+                .collect::<Vec<_>>()
+        };
+        let response = upload_file("hit").await.unwrap();
+        assert!(response.upload_hit);
+        assert_eq!(response.etag, etag);
+        assert_eq!(methods(), vec!["POST"]);
+
+        let response = upload_file("miss").await.unwrap();
+        assert!(!response.upload_hit);
+        assert_eq!(methods(), vec!["POST", "PUT"]);
+    });
+}
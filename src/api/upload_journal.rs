@@ -0,0 +1,197 @@
+//! This module contains the on-disk journal used to resume multipart uploads.
+//!
+//! The journal records the initialized multipart upload task and the etag of every uploaded part.
+//! An interrupted upload continues from the journal with the same upload id, uploading only the
+//! missing parts before the task is finished.
+
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::object::{InitMultipartState, MultipartUploadState},
+    error::UFileError,
+    util::fs::write_atomically,
+};
+
+/// Suffix of the sidecar journal file.
+const JOURNAL_SUFFIX: &str = ".ufjournal";
+
+/// Part uploaded to the multipart upload task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UploadedPart {
+    /// Part number starting at 0.
+    pub part_number: usize,
+    /// ETag of the part returned by the server.
+    pub etag: String,
+}
+
+/// Progress of a multipart upload persisted to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UploadJournal {
+    /// Initialized multipart upload task.
+    pub state: InitMultipartState,
+    /// Local file being uploaded.
+    pub source: PathBuf,
+    /// Size of the local file when the upload started.
+    pub size: u64,
+    /// Modification time of the local file when the upload started, so that a file edited
+    /// without changing its size is not resumed.
+    #[serde(default)]
+    pub modified: Option<SystemTime>,
+    /// Uploaded parts sorted by part number.
+    pub parts: Vec<UploadedPart>,
+}
+
+impl UploadJournal {
+    /// Create an empty journal of the multipart upload task uploading the file with the given
+    /// metadata.
+    pub fn new(state: InitMultipartState, source: impl Into<PathBuf>, metadata: &Metadata) -> Self {
+        Self {
+            state,
+            source: source.into(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            parts: vec![],
+        }
+    }
+
+    /// Path of the sidecar journal file of the source.
+    pub fn path_for(source: &Path) -> PathBuf {
+        let mut path = source.as_os_str().to_owned();
+        path.push(JOURNAL_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// Load the journal, returns `None` if the journal file does not exist.
+    ///
+    /// A corrupt journal file is removed and `None` is returned, so that the upload starts over
+    /// instead of failing until the file is removed by hand.
+    pub fn load(path: &Path) -> Result<Option<Self>, UFileError> {
+        if !path.try_exists()? {
+            return Ok(None);
+        }
+        match serde_json::from_slice(&fs::read(path)?) {
+            Ok(journal) => Ok(Some(journal)),
+            Err(e) => {
+                tracing::warn!("Discard corrupt upload journal {:?}: {}", path, e);
+                fs::remove_file(path)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Save the journal atomically by writing a temporary file, syncing and renaming it.
+    pub fn save(&self, path: &Path) -> Result<(), UFileError> {
+        write_atomically(path, &serde_json::to_vec(self).map_err(io::Error::from)?)?;
+        Ok(())
+    }
+
+    /// Whether the local file with the given metadata is unchanged since the upload started,
+    /// its size and modification time are compared.
+    pub fn is_source_unchanged(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len()
+            && self.modified.is_some()
+            && self.modified == metadata.modified().ok()
+    }
+
+    /// Whether the journal was written for uploading the same, unchanged file to the same object.
+    pub fn is_resumable_for(&self, bucket: &str, key_name: &str, metadata: &Metadata) -> bool {
+        self.state.bucket == bucket
+            && self.state.key_name == key_name
+            && self.is_source_unchanged(metadata)
+    }
+
+    /// Whether the part is already uploaded.
+    pub fn is_completed(&self, part_number: usize) -> bool {
+        self.parts
+            .binary_search_by_key(&part_number, |part| part.part_number)
+            .is_ok()
+    }
+
+    /// Record the uploaded part, replacing the etag of a part uploaded again.
+    pub fn record(&mut self, part_number: usize, etag: impl Into<String>) {
+        let part = UploadedPart {
+            part_number,
+            etag: etag.into(),
+        };
+        match self
+            .parts
+            .binary_search_by_key(&part_number, |part| part.part_number)
+        {
+            Ok(index) => self.parts[index] = part,
+            Err(index) => self.parts.insert(index, part),
+        }
+    }
+
+    /// Part states of the uploaded parts used to finish the multipart upload task.
+    pub fn part_states(&self) -> Vec<MultipartUploadState> {
+        self.parts
+            .iter()
+            .map(|part| MultipartUploadState {
+                headers: Default::default(),
+                part_number: part.part_number,
+                etag: part.etag.clone(),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_upload_journal() {
+    let state = InitMultipartState {
+        upload_id: "upload".into(),
+        blk_size: 4,
+        bucket: "bucket".into(),
+        key_name: "key".into(),
+        mime_type: Some("application/octet-stream".into()),
+    };
+    let dir = crate::util::test_server::temp_dir("journal");
+    let source = dir.join("source");
+    fs::write(&source, "0123456789").unwrap();
+    let metadata = fs::metadata(&source).unwrap();
+    let mut journal = UploadJournal::new(state, &source, &metadata);
+    journal.record(2, "c");
+    journal.record(0, "a");
+    assert!(journal.is_completed(0));
+    assert!(!journal.is_completed(1));
+    journal.record(0, "b");
+    assert_eq!(
+        journal
+            .part_states()
+            .iter()
+            .map(|part| (part.part_number, part.etag.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(0, "b"), (2, "c")]
+    );
+
+    let path = UploadJournal::path_for(&source);
+    journal.save(&path).unwrap();
+    let loaded = UploadJournal::load(&path).unwrap().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.parts, journal.parts);
+    assert!(loaded.is_resumable_for("bucket", "key", &metadata));
+    assert!(!loaded.is_resumable_for("bucket", "other", &metadata));
+    assert!(UploadJournal::load(&path).unwrap().is_none());
+
+    // A file edited in place without changing its size is not resumed.
+    let file = fs::File::options().write(true).open(&source).unwrap();
+    file.set_modified(metadata.modified().unwrap() + std::time::Duration::from_secs(1))
+        .unwrap();
+    assert!(!loaded.is_resumable_for("bucket", "key", &fs::metadata(&source).unwrap()));
+    fs::write(&source, "01234567890").unwrap();
+    assert!(!loaded.is_resumable_for("bucket", "key", &fs::metadata(&source).unwrap()));
+
+    // A journal cut short by a crash is discarded.
+    fs::write(&path, r#"{"State":{"#).unwrap();
+    assert!(UploadJournal::load(&path).unwrap().is_none());
+    assert!(!path.exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,148 @@
+//! This module used to copy a byte range of an existing object into a part of a multipart upload task.
+
+use std::ops::RangeInclusive;
+
+use chrono::Local;
+use reqwest::{Method, header::HeaderMap};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    AuthorizationService,
+    api::{
+        ApiOperation, ObjectOptAuthParamBuilder,
+        copy_file::copy_source,
+        object::{InitMultipartState, MultipartUploadState, response_headers},
+    },
+    define_api_request, define_operation_struct,
+    error::UFileError,
+    retry::RetryMode,
+};
+
+define_operation_struct!(UploadPartCopyOperation);
+
+define_api_request!(
+    UploadPartCopyRequest,
+    UploadPartCopyOperationBuilder,
+    MultipartUploadState,
+    validate,
+    {
+        /// Required: Slice initial state of the destination object.
+        pub state: InitMultipartState,
+
+        /// Required: Index of slices
+        pub part_index: usize,
+
+        /// Required: Source bucket name
+        #[builder(setter(into))]
+        pub src_bucket_name: String,
+
+        /// Required: Source key name
+        #[builder(setter(into))]
+        pub src_key_name: String,
+
+        /// Required: Byte range (inclusive) of the source object copied into this part.
+        pub range: RangeInclusive<u64>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl UploadPartCopyRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            None,
+            None,
+            [(
+                "SecurityToken",
+                self.security_token.as_ref().and_then(Option::as_deref),
+            )],
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for UploadPartCopyOperation {
+    type Request = UploadPartCopyRequest;
+    type Response = MultipartUploadState;
+    type Error = UFileError;
+
+    async fn execute(&self, request: Self::Request) -> Result<Self::Response, Self::Error> {
+        let UploadPartCopyRequest {
+            state,
+            part_index,
+            src_bucket_name,
+            src_key_name,
+            range,
+            security_token,
+            ..
+        } = request;
+        let mime_type = state
+            .mime_type
+            .clone()
+            .ok_or_else(|| UFileError::InvalidRequest("mime type is unset.".to_string()))?;
+        let copy_source = copy_source(src_bucket_name.as_str(), src_key_name.as_str());
+        let copy_source_range = format!("bytes={}-{}", range.start(), range.end());
+        let resp = self
+            .client
+            .send_with_retry(RetryMode::Idempotent, || {
+                // Every attempt is signed with a fresh date.
+                let date = Local::now().format("%Y%m%d%H%M%S").to_string();
+                let auth_object = ObjectOptAuthParamBuilder::default()
+                    .method(Method::PUT)
+                    .bucket(state.bucket.as_str())
+                    .key_name(state.key_name.as_str())
+                    .content_type(mime_type.as_str())
+                    .date(date.as_str())
+                    .x_ufile_copy_source(copy_source.as_str())
+                    .x_ufile_copy_source_range(copy_source_range.as_str())
+                    .build()?;
+                let authorization =
+                    AuthorizationService.authorization(auth_object, self.object_config.clone())?;
+                let mut headers = HeaderMap::new();
+                headers.insert("Content-Type", mime_type.parse()?);
+                headers.insert("Content-Length", "0".parse()?);
+                headers.insert("Accept", "*/*".parse()?);
+                headers.insert("Date", date.parse()?);
+                headers.insert("Authorization", authorization.parse()?);
+                headers.insert("X-Ufile-Copy-Source", copy_source.parse()?);
+                headers.insert("X-Ufile-Copy-Source-Range", copy_source_range.parse()?);
+                if let Some(ref security_token) = security_token
+                    && !security_token.is_empty()
+                {
+                    headers.insert("SecurityToken", security_token.parse()?);
+                }
+                let url = self
+                    .object_config
+                    .generate_final_host(state.bucket.as_str(), state.key_name.as_str());
+                let url = format!(
+                    "{url}?uploadId={}&partNumber={}",
+                    state.upload_id, part_index
+                );
+                Ok(self.client.get_client().put(url).headers(headers))
+            })
+            .await?;
+        tracing::debug!("Upload part copy response: {resp:?}");
+        if resp.status().is_success() {
+            let headers = response_headers(resp.headers());
+            let etag = headers
+                .get("etag")
+                .map(|etag| etag.trim_matches(|c| c == '\"' || c == '\'').to_string())
+                .unwrap_or_default();
+            return Ok(MultipartUploadState {
+                headers,
+                part_number: part_index,
+                etag,
+            });
+        }
+        let error = UFileError::from_response(resp).await;
+        tracing::error!(
+            "Upload part copy of {} range {} failed: {:?}",
+            copy_source,
+            copy_source_range,
+            error
+        );
+        Err(error)
+    }
+}
@@ -0,0 +1,473 @@
+//! This module contains a high level api to upload a stream of unknown length, e.g. stdin or a
+//! compressed tar pipe, to the remote server ucloud.cn.
+//!
+//! The stream is buffered into part sized chunks. If it ends within the first part it is uploaded
+//! with a single put file request, otherwise a multipart upload task is started and the chunks are
+//! uploaded concurrently while the stream is read. At most `concurrency + 1` chunks are held in
+//! memory. The multipart upload task is aborted if any part failed.
+
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{
+    AsyncRead, AsyncReadExt, StreamExt, TryStreamExt,
+    io::{self, Cursor},
+    stream,
+};
+
+use crate::api::validator::are_headers_valid;
+use crate::{
+    api::{
+        ApiOperation, ApiRequest, ByteStream, ByteStreamReader, MultipartAbortRequestBuilder,
+        MultipartFileRequestBuilder, MultipartFinishRequestBuilder, MultipartInitRequestBuilder,
+        ProgressCallback, ProgressTracker, PutFileRequestBuilder, expected_etag,
+        object::{InitMultipartState, MultipartUploadState, UploadFileResponse},
+    },
+    constant::{DEFAULT_CONCURRENCY, MULTIPART_SIZE},
+    define_api_request, define_operation_struct,
+    error::UFileError,
+};
+
+define_operation_struct!(UploadStreamOperation);
+
+define_api_request!(
+    UploadStreamRequest,
+    UploadStreamOperationBuilder,
+    UploadFileResponse,
+    validate,
+    {
+        /// Required: Bucket name
+        #[builder(setter(into))]
+        pub bucket_name: String,
+
+        /// Required: Object key name
+        #[builder(setter(into))]
+        pub key_name: String,
+
+        /// Required: Stream to upload, its length does not need to be known.
+        #[builder(setter(into))]
+        pub stream: ByteStream,
+
+        /// Optional: File MIME type
+        /// Default: application/octet-stream
+        #[builder(setter(into), default = "\"application/octet-stream\".to_string()")]
+        pub mime_type: String,
+
+        /// Optional: Number of parts uploaded concurrently.
+        ///
+        /// Default: 8 from `crate::constant::DEFAULT_CONCURRENCY`
+        #[builder(setter(into, strip_option), default)]
+        pub concurrency: Option<usize>,
+
+        /// Optional: Verify the etag of the uploaded object, and of every part of a multipart
+        /// upload, against the data sent. Fails with
+        /// [`UFileError::IntegrityMismatch`](crate::error::UFileError::IntegrityMismatch) if they
+        /// differ.
+        /// Default: false
+        #[builder(default)]
+        pub verify_integrity: bool,
+
+        /// Optional: Compute and send `Content-MD5` of the put file or of every part.
+        /// Default: false
+        #[builder(default)]
+        pub auto_content_md5: bool,
+
+        /// Optional: Called with the progress of the bytes sent by all requests of the upload,
+        /// the total is the length of the stream if it is known.
+        #[builder(setter(into, strip_option), default)]
+        pub progress: Option<ProgressCallback>,
+
+        /// Optional: User custom metadata
+        #[builder(setter(into, strip_option), default)]
+        pub metadata: Option<::std::collections::HashMap<String, String>>,
+
+        /// Optional: Storage type: STANDARD | IA | ARCHIVE
+        #[builder(setter(into, strip_option), default)]
+        pub storage_type: Option<String>,
+
+        /// Optional: Security token
+        #[builder(setter(into, strip_option), default)]
+        pub security_token: Option<String>,
+    }
+);
+
+impl UploadStreamRequestBuilder {
+    fn validate(&self) -> Result<(), UFileError> {
+        are_headers_valid(
+            self.mime_type.as_deref(),
+            self.metadata.as_ref().and_then(Option::as_ref),
+            [
+                (
+                    "X-Ufile-Storage-Class",
+                    self.storage_type.as_ref().and_then(Option::as_deref),
+                ),
+                (
+                    "SecurityToken",
+                    self.security_token.as_ref().and_then(Option::as_deref),
+                ),
+            ],
+        )
+    }
+}
+
+/// Read up to `size` bytes, less only if the reader reached its end.
+async fn read_chunk(reader: &mut (impl AsyncRead + Unpin), size: usize) -> io::Result<Bytes> {
+    let mut buffer = BytesMut::zeroed(size);
+    let mut filled = 0;
+    while filled < size {
+        match reader.read(&mut buffer[filled..]).await {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    buffer.truncate(filled);
+    Ok(buffer.freeze())
+}
+
+/// Read the next chunk of `blk_size` bytes, `None` once the reader reached its end.
+async fn next_chunk(
+    (mut reader, blk_size): (ByteStreamReader, usize),
+) -> io::Result<Option<(Bytes, (ByteStreamReader, usize))>> {
+    let chunk = read_chunk(&mut reader, blk_size).await?;
+    Ok((!chunk.is_empty()).then_some((chunk, (reader, blk_size))))
+}
+
+impl UploadStreamOperation {
+    /// Upload the whole payload with a single put file request.
+    async fn put_file(
+        &self,
+        request: &UploadStreamRequest,
+        bytes: Bytes,
+        progress_tracker: Option<Arc<ProgressTracker>>,
+    ) -> Result<UploadFileResponse, UFileError> {
+        let response = PutFileRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(request.bucket_name.as_str())
+            .key_name(request.key_name.as_str())
+            .mime_type(request.mime_type.as_str())
+            .stream(ByteStream::from_bytes(bytes))
+            .auto_content_md5(request.auto_content_md5)
+            .verify_etag(request.verify_integrity)
+            .progress_tracker(progress_tracker)
+            .metadatas(request.metadata.clone())
+            .storage_type(request.storage_type.clone())
+            .security_token(request.security_token.clone())
+            .build()?
+            .request()
+            .await?;
+        Ok(UploadFileResponse {
+            headers: response.resp.headers,
+            etag: response.etag,
+            upload_id: None,
+            upload_hit: false,
+        })
+    }
+
+    /// Upload a chunk of the stream as the part at `part_index`.
+    async fn upload_part(
+        &self,
+        state: &InitMultipartState,
+        request: &UploadStreamRequest,
+        part_index: usize,
+        chunk: io::Result<Bytes>,
+        progress_tracker: Option<&Arc<ProgressTracker>>,
+    ) -> Result<MultipartUploadState, UFileError> {
+        let chunk = chunk?;
+        MultipartFileRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .state(state.clone())
+            .buffer(chunk)
+            .part_index(part_index)
+            .auto_content_md5(request.auto_content_md5)
+            .verify_etag(request.verify_integrity)
+            .progress_tracker(progress_tracker.cloned())
+            .security_token(request.security_token.clone())
+            .build()?
+            .request()
+            .await
+    }
+
+    /// Read the stream chunk by chunk and upload the chunks as parts concurrently.
+    async fn upload_parts(
+        &self,
+        state: &InitMultipartState,
+        request: &UploadStreamRequest,
+        reader: ByteStreamReader,
+        progress_tracker: Option<&Arc<ProgressTracker>>,
+    ) -> Result<Vec<MultipartUploadState>, UFileError> {
+        let chunks = stream::try_unfold((reader, state.blk_size as usize), next_chunk);
+        chunks
+            .enumerate()
+            .map(|(part_index, chunk)| {
+                self.upload_part(state, request, part_index, chunk, progress_tracker)
+            })
+            .buffer_unordered(request.concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1))
+            // Boxed to erase the closure type, otherwise the future is not proven to be `Send`.
+            .boxed()
+            .try_collect()
+            .await
+    }
+
+    /// Upload the stream with a multipart upload task, aborted if it failed.
+    async fn multipart_upload(
+        &self,
+        request: &UploadStreamRequest,
+        reader: ByteStreamReader,
+        progress_tracker: Option<Arc<ProgressTracker>>,
+    ) -> Result<UploadFileResponse, UFileError> {
+        let state = MultipartInitRequestBuilder::default()
+            .object_config(self.object_config.clone())
+            .client(self.client.clone())
+            .bucket_name(request.bucket_name.as_str())
+            .key_name(request.key_name.as_str())
+            .mime_type(request.mime_type.as_str())
+            .metadata(request.metadata.clone())
+            .storage_type(request.storage_type.clone())
+            .security_token(request.security_token.clone())
+            .build()?
+            .request()
+            .await?;
+
+        let result = match self
+            .upload_parts(&state, request, reader, progress_tracker.as_ref())
+            .await
+        {
+            Ok(part_states) => match MultipartFinishRequestBuilder::default()
+                .object_config(self.object_config.clone())
+                .client(self.client.clone())
+                .state(state.clone())
+                .expected_etag(if request.verify_integrity {
+                    expected_etag(&state, &part_states)
+                } else {
+                    None
+                })
+                .part_states(part_states)
+                .security_token(request.security_token.clone())
+                .build()
+            {
+                Ok(request) => request.request().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(response) => Ok(UploadFileResponse {
+                headers: response.headers,
+                etag: response.etag,
+                upload_id: Some(state.upload_id),
+                upload_hit: false,
+            }),
+            Err(e) => {
+                tracing::error!(
+                    "Multipart upload of stream to {} failed, abort upload {}: {:?}",
+                    state.key_name,
+                    state.upload_id,
+                    e
+                );
+                let abort_result = match MultipartAbortRequestBuilder::default()
+                    .object_config(self.object_config.clone())
+                    .client(self.client.clone())
+                    .state(state)
+                    .security_token(request.security_token.clone())
+                    .build()
+                {
+                    Ok(request) => request.request().await,
+                    Err(e) => Err(e),
+                };
+                if let Err(abort_error) = abort_result {
+                    tracing::error!("Failed to abort multipart upload: {:?}", abort_error);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiOperation for UploadStreamOperation {
+    type Request = UploadStreamRequest;
+    type Response = UploadFileResponse;
+    type Error = UFileError;
+
+    async fn execute(&self, mut req: Self::Request) -> Result<Self::Response, Self::Error> {
+        let stream = std::mem::replace(&mut req.stream, ByteStream::from_bytes(Bytes::new()));
+        let stream_len = stream.len();
+        let mut reader = stream.into_reader();
+        let progress_tracker = |total_bytes| {
+            req.progress
+                .clone()
+                .map(|callback| Arc::new(ProgressTracker::new(total_bytes, callback)))
+        };
+        let part_size = MULTIPART_SIZE as usize;
+        let first = read_chunk(&mut reader, part_size).await?;
+        if first.len() < part_size {
+            let progress_tracker = progress_tracker(Some(first.len() as u64));
+            return self.put_file(&req, first, progress_tracker).await;
+        }
+        let second = read_chunk(&mut reader, part_size).await?;
+        if second.is_empty() {
+            // The stream ended exactly at the end of the first part.
+            let progress_tracker = progress_tracker(Some(first.len() as u64));
+            return self.put_file(&req, first, progress_tracker).await;
+        }
+        tracing::debug!(
+            "Stream to {} exceeds one part, upload it with multipart upload",
+            req.key_name
+        );
+        // Chunks already read are replayed, since parts may have a different size than 4MB.
+        let reader: ByteStreamReader =
+            Box::pin(Cursor::new(first).chain(Cursor::new(second)).chain(reader));
+        self.multipart_upload(&req, reader, progress_tracker(stream_len))
+            .await
+    }
+}
+
+#[test]
+fn test_read_chunks() {
+    use futures_util::FutureExt;
+
+    // The sources are always ready, the chunks are read without an async runtime.
+    let chunks = |reader: ByteStreamReader, blk_size: usize| {
+        stream::try_unfold((reader, blk_size), next_chunk)
+            .map_ok(|chunk| chunk.to_vec())
+            .try_collect::<Vec<_>>()
+            .now_or_never()
+            .unwrap()
+            .unwrap()
+    };
+    let data = (0..100u8).collect::<Vec<_>>();
+
+    // Reads returning less than asked at the end of the first reader are filled up.
+    let mut reader = Cursor::new(data[..25].to_vec()).chain(Cursor::new(data[25..].to_vec()));
+    let chunk = read_chunk(&mut reader, 40).now_or_never().unwrap().unwrap();
+    assert_eq!(chunk, data[..40]);
+    let reader: ByteStreamReader =
+        Box::pin(Cursor::new(data[..25].to_vec()).chain(Cursor::new(data[25..].to_vec())));
+    assert_eq!(
+        chunks(reader, 40),
+        [&data[..40], &data[40..80], &data[80..]]
+    );
+
+    // A stream ending on a part boundary has no empty last chunk.
+    let reader: ByteStreamReader = Box::pin(Cursor::new(data.clone()));
+    assert_eq!(chunks(reader, 50), [&data[..50], &data[50..]]);
+
+    let reader: ByteStreamReader = Box::pin(Cursor::new(Vec::new()));
+    assert!(chunks(reader, 50).is_empty());
+}
+
+#[test]
+fn test_upload_stream() {
+    use crate::{client::HttpClient, util::test_server};
+
+    test_server::block_on(async {
+        // Parts of 3MB, so that the first two chunks read in 4MB are split again.
+        let blk_size = 3 << 20;
+        let (object_config, log) = test_server::serve(move |_, received| {
+            let key = received.uri[1..].split('?').next().unwrap().to_string();
+            match received.method.as_str() {
+                "POST" if received.uri.ends_with("?uploads") => {
+                    let body = format!(
+                        r#"{{"UploadId":"{key}-upload","BlkSize":{blk_size},"Bucket":"bucket","Key":"{key}"}}"#
+                    );
+                    (200, vec![], body)
+                }
+                "PUT" => match received.uri.split_once("partNumber=") {
+                    Some((_, "1")) if key == "broken" => {
+                        let body = r#"{"RetCode":-1,"ErrMsg":"part failed"}"#;
+                        (400, vec![], body.to_string())
+                    }
+                    Some((_, part_number)) => (
+                        200,
+                        vec![("ETag", format!("\"etag-{part_number}\""))],
+                        format!(r#"{{"PartNumber":{part_number}}}"#),
+                    ),
+                    None => (200, vec![("ETag", "\"put\"".to_string())], String::new()),
+                },
+                "POST" => (
+                    200,
+                    vec![("ETag", "\"multipart\"".to_string())],
+                    format!(r#"{{"Bucket":"bucket","Key":"{key}","FileSize":0}}"#),
+                ),
+                _ => (204, vec![], String::new()),
+            }
+        })
+        .await;
+        let upload = |key_name: &str, data: &[u8]| {
+            UploadStreamRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(HttpClient::builder().build().unwrap())
+                .bucket_name("bucket")
+                .key_name(key_name)
+                .stream(ByteStream::from_reader(Cursor::new(data.to_vec())))
+                .concurrency(2usize)
+                .build()
+                .unwrap()
+                .request()
+        };
+        let requests = || std::mem::take(&mut *log.lock().unwrap());
+        let part_size = MULTIPART_SIZE as usize;
+        let data = (0..2 * part_size + 5)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        // Streams within one part are put, also if they end exactly at the end of the part.
+        for len in [10, part_size] {
+            let response = upload("small", &data[..len]).await.unwrap();
+            assert_eq!(response.upload_id, None);
+            let received = requests();
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].method, "PUT");
+            assert_eq!(received[0].uri, "/small");
+            assert_eq!(received[0].body, data[..len]);
+        }
+
+        // Init, the parts replaying the chunks read before, and finish with the ordered etags.
+        let response = upload("large", &data).await.unwrap();
+        assert_eq!(response.upload_id.as_deref(), Some("large-upload"));
+        let mut received = requests();
+        assert_eq!(received.len(), 5);
+        assert_eq!(received.remove(0).uri, "/large?uploads");
+        let finish = received.pop().unwrap();
+        assert_eq!(finish.uri, "/large?uploadId=large-upload&newKey=");
+        assert_eq!(finish.body, b"etag-0,etag-1,etag-2");
+        received.sort_by(|a, b| a.uri.cmp(&b.uri));
+        assert_eq!(
+            received
+                .iter()
+                .map(|received| received.uri.as_str())
+                .collect::<Vec<_>>(),
+            (0..3)
+                .map(|i| format!("/large?uploadId=large-upload&partNumber={i}"))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            received
+                .into_iter()
+                .flat_map(|received| received.body)
+                .collect::<Vec<_>>(),
+            data
+        );
+
+        // The upload is aborted instead of finished if a part failed.
+        let error = upload("broken", &data).await.unwrap_err();
+        assert_eq!(error.message(), Some("part failed"));
+        let received = requests();
+        assert!(
+            !received
+                .iter()
+                .any(|received| received.uri.contains("newKey="))
+        );
+        // Parts in flight when the part failed may arrive after the abort.
+        let abort = received
+            .iter()
+            .find(|received| received.method == "DELETE")
+            .unwrap();
+        assert_eq!(abort.uri, "/broken?uploadId=broken-upload");
+    });
+}
@@ -2,13 +2,16 @@
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Error;
 use derive_builder::Builder;
 use reqwest::Method;
 
-use crate::api::{ApiOperation, ObjectConfig, Sealed};
+use crate::{
+    api::{ApiOperation, ObjectConfig, Sealed},
+    error::UFileError,
+};
 
 #[derive(Builder)]
+#[builder(build_fn(error = "UFileError"))]
 pub struct GenPublicUrlRequest {
     /// Requried: Bucket name.
     #[builder(setter(into))]
@@ -38,9 +41,9 @@ impl Sealed for GenPublicUrlOperation {}
 impl ApiOperation for GenPublicUrlOperation {
     type Request = GenPublicUrlRequest;
     type Response = String;
-    type Error = Error;
+    type Error = UFileError;
 
-    async fn execute(&self, req: Self::Request) -> Result<String, Error> {
+    async fn execute(&self, req: Self::Request) -> Result<String, UFileError> {
         let GenPublicUrlRequest {
             bucket_name,
             key_name,
@@ -59,6 +62,7 @@ impl ApiOperation for GenPublicUrlOperation {
 
 /// Request for generating private URL which will be expired in `expires` seconds.
 #[derive(Builder)]
+#[builder(build_fn(error = "UFileError"))]
 pub struct GenPrivateUrlRequest {
     /// Required: Bucket name.
     #[builder(setter(into))]
@@ -106,9 +110,9 @@ impl Sealed for GenPrivateUrlOperation {}
 impl ApiOperation for GenPrivateUrlOperation {
     type Request = GenPrivateUrlRequest;
     type Response = String;
-    type Error = Error;
+    type Error = UFileError;
 
-    async fn execute(&self, req: Self::Request) -> Result<String, Error> {
+    async fn execute(&self, req: Self::Request) -> Result<String, UFileError> {
         let GenPrivateUrlRequest {
             bucket_name,
             key_name,
@@ -118,8 +122,12 @@ impl ApiOperation for GenPrivateUrlOperation {
             iop_cmd,
         } = req;
         // calculate expire time since epoch time: (now - 1970-01-01 00:00:00) + expires
-        let expire_time =
-            (expires + SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()).to_string();
+        let expire_time = (expires
+            + SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| UFileError::InvalidRequest(e.to_string()))?
+                .as_secs())
+        .to_string();
 
         let signature = self.object_config.authorization_private_url(
             Method::GET,
@@ -1,9 +1,12 @@
 //! This module provides validator functions for api field's validation checking.
 #![allow(unused)]
-use std::{borrow::Cow, fs::File as StdFile, path::PathBuf};
+use std::{borrow::Cow, collections::HashMap, fs::File as StdFile, path::PathBuf};
 
 use bytes::Bytes;
 use mediatype::MediaType;
+use reqwest::header::{HeaderName, HeaderValue};
+
+use crate::error::UFileError;
 
 /// Check the given key is not empty.
 fn is_not_empty(key: String, error_msg: &'static str) -> Result<String, &'static str> {
@@ -98,3 +101,71 @@ pub(crate) fn is_buffer_not_empty(buffer: Bytes) -> Result<Bytes, &'static str>
     }
     Ok(buffer)
 }
+
+/// Check the user metadata can be sent as `X-Ufile-Meta-*` headers. Values are limited to
+/// printable ASCII, since ucloud.cn returns them as is, other characters have to be encoded.
+pub(crate) fn is_metadata_valid(metadata: &HashMap<String, String>) -> Result<(), String> {
+    for (key, value) in metadata {
+        if key.is_empty() || HeaderName::try_from(format!("X-Ufile-Meta-{key}")).is_err() {
+            return Err(format!("metadata key [{key}] is not a valid header name"));
+        }
+        if !value.chars().all(|c| c == '\t' || (' '..='~').contains(&c)) {
+            return Err(format!(
+                "metadata value of [{key}] must be printable ASCII, encode it e.g. with percent-encoding"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check the given value can be sent as the header `name`.
+pub(crate) fn is_header_value_valid(name: &str, value: &str) -> Result<(), String> {
+    HeaderValue::from_str(value)
+        .map(|_| ())
+        .map_err(|_| format!("{name} [{value}] is not a valid header value"))
+}
+
+/// Check the header values of a request given by the user, so that building the request fails
+/// instead of sending it.
+pub(crate) fn are_headers_valid<'a>(
+    mime_type: Option<&str>,
+    metadata: Option<&HashMap<String, String>>,
+    values: impl IntoIterator<Item = (&'static str, Option<&'a str>)>,
+) -> Result<(), UFileError> {
+    if let Some(mime_type) = mime_type {
+        is_mime_type_valid(mime_type.to_string())
+            .map_err(|e| UFileError::InvalidRequest(e.into_owned()))?;
+    }
+    if let Some(metadata) = metadata {
+        is_metadata_valid(metadata).map_err(UFileError::InvalidRequest)?;
+    }
+    for (name, value) in values {
+        if let Some(value) = value {
+            is_header_value_valid(name, value).map_err(UFileError::InvalidRequest)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_are_headers_valid() {
+    let metadata = HashMap::from([("owner".to_string(), "me".to_string())]);
+    assert!(
+        are_headers_valid(
+            Some("text/plain"),
+            Some(&metadata),
+            [("SecurityToken", Some("t"))]
+        )
+        .is_ok()
+    );
+    assert!(are_headers_valid(Some("text plain"), None, []).is_err());
+    assert!(are_headers_valid(Some(""), None, []).is_err());
+
+    let metadata = HashMap::from([("owner".to_string(), "café".to_string())]);
+    assert!(are_headers_valid(None, Some(&metadata), []).is_err());
+    let metadata = HashMap::from([("own er".to_string(), "me".to_string())]);
+    assert!(are_headers_valid(None, Some(&metadata), []).is_err());
+
+    assert!(are_headers_valid(None, None, [("SecurityToken", Some("a\nb"))]).is_err());
+    assert!(are_headers_valid(None, None, [("SecurityToken", None)]).is_ok());
+}
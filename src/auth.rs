@@ -1,23 +1,26 @@
-use anyhow::Error;
 use base64::Engine;
 use hmac::{Hmac, Mac};
 use sha1::Sha1;
 
-use crate::api::{ObjectConfig, ObjectOptAuthParam};
+use crate::{
+    api::{ObjectConfig, ObjectOptAuthParam},
+    error::UFileError,
+};
 
 // 签名器 trait
 pub trait Signer {
     /// Method used to sign data.
-    fn signature(&self, private_key: &str, data: &str) -> Result<String, Error>;
+    fn signature(&self, private_key: &str, data: &str) -> Result<String, UFileError>;
 }
 
 pub struct HmacSha1Signer;
 
 impl Signer for HmacSha1Signer {
-    fn signature(&self, private_key: &str, data: &str) -> Result<String, Error> {
+    fn signature(&self, private_key: &str, data: &str) -> Result<String, UFileError> {
         type HmacSha1 = Hmac<Sha1>;
 
-        let mut mac = HmacSha1::new_from_slice(private_key.as_bytes())?;
+        let mut mac = HmacSha1::new_from_slice(private_key.as_bytes())
+            .map_err(|e| UFileError::InvalidRequest(format!("invalid private key: {e}")))?;
         mac.update(data.as_bytes());
         let result = mac.finalize();
         let code_bytes = result.into_bytes();
@@ -31,11 +34,16 @@ impl Signer for HmacSha1Signer {
 #[test]
 fn test_signature() {
     let signer = HmacSha1Signer;
-    let signature = signer.signature("46e8d7f3-3319-4b69-bfa3-86c00dd9c978", "GET
+    let signature = signer
+        .signature(
+            "46e8d7f3-3319-4b69-bfa3-86c00dd9c978",
+            "GET
 
 
 1757756531
-/guilaixi/user_event/9cb9563021f75fd648c3fe905bce9a1898cb147b8a3bfedac9d8845c190e7edb").unwrap();
+/guilaixi/user_event/9cb9563021f75fd648c3fe905bce9a1898cb147b8a3bfedac9d8845c190e7edb",
+        )
+        .unwrap();
     println!("{signature}");
     println!("{}", urlencoding::encode("wmX3jd16tkEzZSzggqZLNIwcjRs="));
 }
@@ -49,7 +57,7 @@ impl AuthorizationService {
         &self,
         param: ObjectOptAuthParam,
         object_config: ObjectConfig,
-    ) -> Result<String, Error> {
+    ) -> Result<String, UFileError> {
         let method = &param.method;
         let bucket = param.bucket.as_str();
         let key_name = param.key_name.as_str();
@@ -1,15 +1,33 @@
-use std::{collections::HashMap, str::FromStr, time::Duration};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use crate::{
     AuthorizationService,
     api::{
-        BaseResponse, ByteStream, GenPrivateUrlRequestBuilder, HeadFileRequestBuilder,
-        MultipartAbortRequestBuilder, MultipartFileRequestBuilder, MultipartFinishRequestBuilder,
-        MultipartInitRequestBuilder, ObjectConfig, ProgressStream, PutFileRequestBuilder,
+        AbortStaleUploadsRequestBuilder, BaseResponse, ByteStream, ByteStreamReader,
+        CopyFileRequestBuilder, DeleteFileRequestBuilder, GenPrivateUrlRequestBuilder,
+        GetObjectRequestBuilder, HeadFileRequestBuilder, ListMultipartUploadsRequestBuilder,
+        ListObjectsRequestBuilder, ListObjectsStreamOptions, ListPartsRequestBuilder,
+        MultipartAbortRequestBuilder, MultipartCopyRequestBuilder, MultipartFileRequestBuilder,
+        MultipartFinishRequestBuilder, MultipartInitRequestBuilder,
+        MultipartUploadPartsRequestBuilder, ObjectConfig, ObjectSummary, ProgressStream,
+        PutFileRequestBuilder, RenameFileRequestBuilder, ResumeUploadRequestBuilder,
+        UploadFileRequestBuilder, UploadHitRequestBuilder, UploadPartCopyRequestBuilder,
+        UploadStreamRequestBuilder, buffer_pool, list_objects_stream,
     },
+    error::UFileError,
+    retry::{RetryMode, RetryPolicy, sleep},
+    util::{ETagHasher, pool::ObjectPool},
+};
+use bytes::BytesMut;
+use futures_util::Stream;
+use reqwest::{
+    Body, Client, ClientBuilder, Method, Proxy, RequestBuilder, Response, Url, header::HeaderMap,
 };
-use anyhow::Error;
-use reqwest::{Body, Client, ClientBuilder, Method, Proxy, Url, header::HeaderMap};
 
 #[derive(Clone)]
 pub struct S3Client {
@@ -17,6 +35,12 @@ pub struct S3Client {
     auth_service: AuthorizationService,
 }
 
+impl Default for S3Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl S3Client {
     pub fn new() -> Self {
         Self {
@@ -30,6 +54,12 @@ impl S3Client {
         self
     }
 
+    /// Retry policy of the requests sent by the http client.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.http_client = self.http_client.with_retry_policy(retry_policy);
+        self
+    }
+
     pub fn with_auth_service(mut self, auth_service: AuthorizationService) -> Self {
         self.auth_service = auth_service;
         self
@@ -51,6 +81,37 @@ impl S3Client {
             .client(self.http_client())
     }
 
+    /// Upload request builder which uses a single put file request for small sources and
+    /// a concurrent multipart upload for large ones.
+    pub fn upload(&self, object_config: ObjectConfig) -> UploadFileRequestBuilder {
+        UploadFileRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Upload request builder for streams of unknown length, which are buffered into parts and
+    /// uploaded with a multipart upload unless they fit into a single part.
+    pub fn upload_stream(&self, object_config: ObjectConfig) -> UploadStreamRequestBuilder {
+        UploadStreamRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Resume an interrupted multipart upload of a file from its journal request builder.
+    pub fn resume_upload(&self, object_config: ObjectConfig) -> ResumeUploadRequestBuilder {
+        ResumeUploadRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Upload hit request builder, creates the key from a stored file with the same UFile ETag
+    /// and size without transferring data.
+    pub fn upload_hit(&self, object_config: ObjectConfig) -> UploadHitRequestBuilder {
+        UploadHitRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
     /// Init multipart upload request builder.
     pub fn multipart_init(&self, object_config: ObjectConfig) -> MultipartInitRequestBuilder {
         MultipartInitRequestBuilder::default()
@@ -65,6 +126,43 @@ impl S3Client {
             .client(self.http_client())
     }
 
+    /// List in-progress multipart uploads of a bucket request builder.
+    pub fn list_multipart_uploads(
+        &self,
+        object_config: ObjectConfig,
+    ) -> ListMultipartUploadsRequestBuilder {
+        ListMultipartUploadsRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Abort every multipart upload older than an age under a prefix request builder.
+    pub fn abort_stale_uploads(
+        &self,
+        object_config: ObjectConfig,
+    ) -> AbortStaleUploadsRequestBuilder {
+        AbortStaleUploadsRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// List uploaded parts of a multipart upload request builder.
+    pub fn list_parts(&self, object_config: ObjectConfig) -> ListPartsRequestBuilder {
+        ListPartsRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Upload every part of a local file with bounded memory request builder.
+    pub fn multipart_upload_parts(
+        &self,
+        object_config: ObjectConfig,
+    ) -> MultipartUploadPartsRequestBuilder {
+        MultipartUploadPartsRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
     /// Finish multipart upload request builder.
     pub fn multipart_finish(&self, object_config: ObjectConfig) -> MultipartFinishRequestBuilder {
         MultipartFinishRequestBuilder::default()
@@ -79,6 +177,40 @@ impl S3Client {
             .client(self.http_client())
     }
 
+    /// Copy a byte range of an existing object into a multipart upload part request builder.
+    pub fn upload_part_copy(&self, object_config: ObjectConfig) -> UploadPartCopyRequestBuilder {
+        UploadPartCopyRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Server side multipart copy request builder, used for objects larger than the single copy limit.
+    pub fn multipart_copy(&self, object_config: ObjectConfig) -> MultipartCopyRequestBuilder {
+        MultipartCopyRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Get object content as a stream request builder, supports ranged and conditional requests.
+    pub fn get_object(&self, object_config: ObjectConfig) -> GetObjectRequestBuilder {
+        GetObjectRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Download object to a local file with concurrent ranged requests request builder.
+    ///
+    /// Only supported on unix like systems.
+    #[cfg(feature = "download")]
+    pub fn download_to_path(
+        &self,
+        object_config: ObjectConfig,
+    ) -> crate::api::DownloadFileRequestBuilder {
+        crate::api::DownloadFileRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
     /// Get file heads request builder.
     pub fn head_object(&self, object_config: ObjectConfig) -> HeadFileRequestBuilder {
         HeadFileRequestBuilder::default()
@@ -86,20 +218,72 @@ impl S3Client {
             .client(self.http_client())
     }
 
+    /// Server side copy file request builder.
+    pub fn copy_object(&self, object_config: ObjectConfig) -> CopyFileRequestBuilder {
+        CopyFileRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// Delete file request builder.
+    pub fn delete_object(&self, object_config: ObjectConfig) -> DeleteFileRequestBuilder {
+        DeleteFileRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// List objects of a bucket request builder.
+    pub fn list_objects(&self, object_config: ObjectConfig) -> ListObjectsRequestBuilder {
+        ListObjectsRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
+    /// List every object of a bucket under `prefix` as a stream, following the next marker
+    /// transparently.
+    pub fn list_objects_stream(
+        &self,
+        object_config: ObjectConfig,
+        bucket_name: impl Into<String>,
+        prefix: impl Into<String>,
+        options: ListObjectsStreamOptions,
+    ) -> impl Stream<Item = Result<ObjectSummary, UFileError>> + Send + 'static {
+        list_objects_stream(
+            object_config,
+            self.http_client(),
+            bucket_name.into(),
+            prefix.into(),
+            options,
+        )
+    }
+
+    /// Rename file in place request builder.
+    ///
+    /// If `force` is false and the new key already exists, the request fails with
+    /// [`UFileError::ObjectAlreadyExists`](crate::error::UFileError::ObjectAlreadyExists).
+    pub fn rename_object(&self, object_config: ObjectConfig) -> RenameFileRequestBuilder {
+        RenameFileRequestBuilder::default()
+            .object_config(object_config)
+            .client(self.http_client())
+    }
+
     /// Generate private url request builder.
     pub fn gen_private_url(&self) -> GenPrivateUrlRequestBuilder {
         GenPrivateUrlRequestBuilder::default()
     }
 }
 
-#[repr(transparent)]
 #[derive(Clone)]
 pub struct HttpClient {
     inner: Client,
+    retry_policy: RetryPolicy,
+    /// Buffers of the streams sent, shared by the clones of the client.
+    buffer_pool: ObjectPool<BytesMut>,
 }
 
 pub struct HttpClientBuilder {
     builder: ClientBuilder,
+    retry_policy: RetryPolicy,
 }
 
 impl HttpClient {
@@ -111,6 +295,20 @@ impl HttpClient {
         &self.inner
     }
 
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Pool of the buffers files and readers are read into when they are sent.
+    pub fn buffer_pool(&self) -> &ObjectPool<BytesMut> {
+        &self.buffer_pool
+    }
+
     pub fn into_inner(self) -> Client {
         self.inner
     }
@@ -130,6 +328,7 @@ impl HttpClientBuilder {
                 // only support http1
                 .http1_only()
                 .user_agent(format!("ufile-rus3-sdk/{}", crate::VERSION)),
+            retry_policy: RetryPolicy::default(),
         }
     }
 
@@ -168,9 +367,17 @@ impl HttpClientBuilder {
         self
     }
 
-    pub fn build(self) -> Result<HttpClient, Error> {
+    /// Retry policy of the requests, see [`RetryPolicy`].
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn build(self) -> Result<HttpClient, UFileError> {
         Ok(HttpClient {
             inner: self.builder.build()?,
+            retry_policy: self.retry_policy,
+            buffer_pool: buffer_pool(),
         })
     }
 }
@@ -180,45 +387,389 @@ impl Default for HttpClientBuilder {
     }
 }
 impl HttpClient {
+    /// Send the request built by `build`, and send it again according to the retry policy if it
+    /// failed transiently.
+    ///
+    /// The request is built again for every attempt, so that it is signed with a fresh `Date` and
+    /// its body is replayed. The response of the last attempt is returned.
+    pub async fn send_with_retry<F>(
+        &self,
+        mode: RetryMode,
+        mut build: F,
+    ) -> Result<Response, UFileError>
+    where
+        F: FnMut() -> Result<RequestBuilder, UFileError>,
+    {
+        let policy = &self.retry_policy;
+        let mut attempt = 1;
+        loop {
+            let (result, retryable) = match build()?.send().await {
+                Ok(response) if response.status().is_success() => (Ok(response), false),
+                Ok(response) if policy.is_retryable_status(response.status().as_u16(), mode) => {
+                    (Ok(response), true)
+                }
+                Ok(response) if policy.checks_ret_code(mode) => {
+                    let (response, ret_code) = peek_ret_code(response).await?;
+                    let retryable = ret_code
+                        .is_some_and(|ret_code| policy.is_retryable_ret_code(ret_code, mode));
+                    (Ok(response), retryable)
+                }
+                Ok(response) => (Ok(response), false),
+                Err(error) => {
+                    let retryable = policy.is_retryable_error(&error, mode);
+                    (Err(error), retryable)
+                }
+            };
+            if !retryable || attempt >= policy.max_attempts {
+                return Ok(result?);
+            }
+            let backoff = policy.backoff(attempt);
+            tracing::warn!(
+                "Request attempt {}/{} failed, retry in {:?}: {:?}",
+                attempt,
+                policy.max_attempts,
+                backoff,
+                result.as_ref().map(Response::status)
+            );
+            sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+
     /// This method support only files which is smaller than 512MB,
     /// otherwise, will return error.
     /// If you are trying to upload a file that is more than 512MB, please use multipart upload which
     /// is supported by ucloud (Which should create multiple slices to upload).
+    ///
+    /// In memory bytes and files are sent again according to the retry policy, with the headers
+    /// signed by the caller. Readers can only be sent once and are never retried.
     pub async fn send_file(
         &self,
         url: &str,
         method: Method,
         headers: HeaderMap,
         stream: ByteStream,
-    ) -> Result<BaseResponse, Error> {
-        // Check authorization
-        let signature = headers.get("Authorization");
-        if signature.is_none() {
-            return Err(Error::msg("No authorization header found"));
-        }
+    ) -> Result<BaseResponse, UFileError> {
+        self.send_file_with_etag_hasher(url, method, headers, stream, None)
+            .await
+    }
+
+    /// Send the file like [`HttpClient::send_file`], feeding every byte sent to the hasher.
+    ///
+    /// The hasher is reset to its state before the first attempt by every retried attempt.
+    pub async fn send_file_with_etag_hasher(
+        &self,
+        url: &str,
+        method: Method,
+        headers: HeaderMap,
+        stream: ByteStream,
+        etag_hasher: Option<Arc<Mutex<ETagHasher>>>,
+    ) -> Result<BaseResponse, UFileError> {
+        let url = file_url(url, &headers)?;
+        let initial_hasher = etag_hasher
+            .as_ref()
+            .map(|etag_hasher| etag_hasher.lock().unwrap().clone());
+        // In memory bytes and files can be sent again, other streams only once.
+        let mode = if stream.is_replayable() {
+            RetryMode::Idempotent
+        } else {
+            RetryMode::Never
+        };
+        let mut stream = Some(stream);
+        let response = self
+            .send_with_retry(mode, || {
+                let stream = match stream.as_ref().and_then(ByteStream::replay) {
+                    Some(replayed) => replayed?,
+                    None => stream.take().ok_or_else(|| {
+                        UFileError::InvalidRequest("stream can not be sent again.".to_string())
+                    })?,
+                };
+                let mut body =
+                    ProgressStream::from(stream).with_buffer_pool(self.buffer_pool.clone());
+                if let (Some(etag_hasher), Some(initial_hasher)) = (&etag_hasher, &initial_hasher) {
+                    *etag_hasher.lock().unwrap() = initial_hasher.clone();
+                    body = body.with_etag_hasher(etag_hasher.clone());
+                }
+                Ok(self
+                    .inner
+                    .request(method.clone(), url.clone())
+                    .headers(headers.clone())
+                    .body(Body::wrap_stream(body)))
+            })
+            .await?;
+        tracing::debug!("send file response: {:?}", response);
+        base_response(response).await
+    }
+
+    /// Send the file like [`HttpClient::send_file`] from a progress stream, which may hash the
+    /// bytes sent or report them to a progress tracker.
+    ///
+    /// The stream can only be read once, so the request is never retried.
+    pub async fn send_progress_stream(
+        &self,
+        url: &str,
+        method: Method,
+        headers: HeaderMap,
+        stream: ProgressStream<ByteStreamReader>,
+    ) -> Result<BaseResponse, UFileError> {
+        let url = file_url(url, &headers)?;
         let response = self
             .inner
-            .request(method, Url::from_str(url)?)
+            .request(method, url)
             .headers(headers)
-            .body(Body::wrap_stream(ProgressStream::from(stream)))
+            .body(Body::wrap_stream(stream))
             .send()
             .await?;
         tracing::debug!("send file response: {:?}", response);
-        let response_headers = response
-            .headers()
-            .iter()
-            .map(|(key, value)| Ok((key.to_string(), String::from_utf8(value.as_bytes().into())?)))
-            .collect::<Result<HashMap<String, String>, Error>>()?;
-        let status = response.status();
-        Ok(if status.is_success() {
-            // 2xx
-            BaseResponse {
-                headers: response_headers,
-                ret_code: 0,
-                message: None,
-            }
-        } else {
-            response.json::<BaseResponse>().await?
+        base_response(response).await
+    }
+}
+
+/// Parse the url of a request sending a file, whose headers must be signed.
+fn file_url(url: &str, headers: &HeaderMap) -> Result<Url, UFileError> {
+    // Check authorization
+    if !headers.contains_key("Authorization") {
+        return Err(UFileError::InvalidRequest(
+            "No authorization header found".to_string(),
+        ));
+    }
+    Url::from_str(url).map_err(|e| UFileError::InvalidRequest(format!("invalid url {url}: {e}")))
+}
+
+/// Convert the response of a request sending a file, the `RetCode` of successful responses is 0.
+/// Unsuccessful responses are converted to [`UFileError::ApiError`].
+pub(crate) async fn base_response(response: Response) -> Result<BaseResponse, UFileError> {
+    let response_headers = response
+        .headers()
+        .iter()
+        .map(|(key, value)| {
+            let value = String::from_utf8(value.as_bytes().into()).map_err(|_| {
+                UFileError::InvalidResponse(format!("header {key} is not valid utf-8"))
+            })?;
+            Ok((key.to_string(), value))
         })
+        .collect::<Result<HashMap<String, String>, UFileError>>()?;
+    if !response.status().is_success() {
+        return Err(UFileError::from_response(response).await);
+    }
+    Ok(BaseResponse {
+        headers: response_headers,
+        ret_code: 0,
+        message: None,
+    })
+}
+
+/// Read the `RetCode` of an error response, the response is rebuilt from the body read.
+async fn peek_ret_code(response: Response) -> Result<(Response, Option<i32>), UFileError> {
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    let body = response.bytes().await?;
+    let ret_code = serde_json::from_slice::<BaseResponse>(&body)
+        .ok()
+        .map(|response| response.ret_code);
+    let response = builder
+        .body(body)
+        .map_err(|e| UFileError::InvalidResponse(e.to_string()))?;
+    Ok((Response::from(response), ret_code))
+}
+
+#[test]
+fn test_send_with_retry() {
+    use futures_util::io::Cursor;
+
+    use crate::{
+        AuthorizationService,
+        api::{ApiRequest, ObjectOptAuthParamBuilder},
+        util::test_server::{self, Reply, block_on, serve},
+    };
+
+    fn unavailable() -> Reply {
+        (503, vec![], r#"{"RetCode":-1,"ErrMsg":"busy"}"#.to_string())
     }
+    let client = |initial_backoff| {
+        HttpClient::builder()
+            .with_retry_policy(RetryPolicy {
+                initial_backoff,
+                jitter: false,
+                ..RetryPolicy::default()
+            })
+            .build()
+            .unwrap()
+    };
+
+    block_on(async {
+        // A 503 is retried for idempotent requests only.
+        let (object_config, log) = serve(|index, _| match index {
+            0 | 1 => unavailable(),
+            _ => (200, vec![], String::new()),
+        })
+        .await;
+        let http_client = client(Duration::from_millis(1));
+        let url = object_config.generate_final_host("bucket", "key");
+        let send = |mode| {
+            http_client.send_with_retry(mode, || Ok(http_client.get_client().get(url.as_str())))
+        };
+        assert_eq!(send(RetryMode::Idempotent).await.unwrap().status(), 200);
+        assert_eq!(log.lock().unwrap().len(), 3);
+
+        let (object_config, log) = serve(|_, _| unavailable()).await;
+        let url = object_config.generate_final_host("bucket", "key");
+        let send = |mode| {
+            http_client.send_with_retry(mode, || Ok(http_client.get_client().get(url.as_str())))
+        };
+        for mode in [RetryMode::ConnectOnly, RetryMode::Never] {
+            log.lock().unwrap().clear();
+            assert_eq!(send(mode).await.unwrap().status(), 503);
+            assert_eq!(log.lock().unwrap().len(), 1);
+        }
+
+        // Every attempt of in memory bytes is signed for its own date and sends the bytes again.
+        let (object_config, log) = serve(|index, _| match index {
+            0 => unavailable(),
+            _ => (200, vec![], String::new()),
+        })
+        .await;
+        let put = |stream| {
+            PutFileRequestBuilder::default()
+                .object_config(object_config.clone())
+                .client(client(Duration::from_millis(1)))
+                .bucket_name("bucket")
+                .key_name("key")
+                .mime_type("text/plain")
+                .stream(stream)
+                .build()
+                .unwrap()
+                .request()
+        };
+        put(ByteStream::from_bytes("data".into())).await.unwrap();
+        let received = log.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        for received in &received {
+            let date = &received.headers["date"];
+            assert!(chrono::NaiveDateTime::parse_from_str(date, "%Y%m%d%H%M%S").is_ok());
+            let auth_object = ObjectOptAuthParamBuilder::default()
+                .method(reqwest::Method::PUT)
+                .bucket("bucket")
+                .key_name("key")
+                .content_type("text/plain")
+                .date(date.as_str())
+                .build()
+                .unwrap();
+            assert_eq!(
+                received.headers["authorization"],
+                AuthorizationService
+                    .authorization(auth_object, object_config.clone())
+                    .unwrap()
+            );
+            assert_eq!(received.body, b"data");
+        }
+
+        // A file is opened again for every attempt.
+        log.lock().unwrap().clear();
+        let dir = test_server::temp_dir("put-retry");
+        let path = dir.join("file");
+        std::fs::write(&path, "file data").unwrap();
+        put(ByteStream::from_path(&path).unwrap()).await.unwrap();
+        let received = log.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        assert!(
+            received
+                .iter()
+                .all(|received| received.body == b"file data")
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A reader can not be sent again.
+        let (object_config, log) = serve(|_, _| unavailable()).await;
+        let error = PutFileRequestBuilder::default()
+            .object_config(object_config)
+            .client(client(Duration::from_millis(1)))
+            .bucket_name("bucket")
+            .key_name("key")
+            .mime_type("text/plain")
+            .stream(ByteStream::from_reader(Cursor::new(b"data".to_vec())).with_len(4))
+            .build()
+            .unwrap()
+            .request()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.status(),
+            Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(log.lock().unwrap().len(), 1);
+        assert_eq!(log.lock().unwrap()[0].body, b"data");
+    });
+}
+
+#[test]
+fn test_send_file() {
+    use futures_util::io::Cursor;
+
+    use crate::util::{
+        ETag,
+        test_server::{block_on, serve},
+    };
+
+    block_on(async {
+        let client = HttpClient::builder()
+            .with_retry_policy(RetryPolicy {
+                initial_backoff: Duration::from_millis(1),
+                jitter: false,
+                ..RetryPolicy::default()
+            })
+            .build()
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "UCloud public:signature".parse().unwrap());
+        let unavailable = || (503, vec![], "<html>Service Unavailable</html>".to_string());
+
+        // In memory bytes are sent again, and hashed once.
+        let (object_config, log) = serve(move |index, _| match index {
+            0 => unavailable(),
+            _ => (200, vec![("ETag", "\"etag\"".to_string())], String::new()),
+        })
+        .await;
+        let url = object_config.generate_final_host("bucket", "key");
+        let etag_hasher = Arc::new(Mutex::new(ETagHasher::new()));
+        let response = client
+            .send_file_with_etag_hasher(
+                &url,
+                Method::PUT,
+                headers.clone(),
+                ByteStream::from_bytes("data".into()),
+                Some(etag_hasher.clone()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.ret_code, 0);
+        assert_eq!(response.headers["etag"], "\"etag\"");
+        assert_eq!(
+            etag_hasher.lock().unwrap().finalize().etag,
+            ETag::from_bytes(b"data").etag
+        );
+        let received = log.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        assert!(received.iter().all(|received| received.body == b"data"));
+
+        // A reader is sent once, the error response without a json body is an api error.
+        let (object_config, log) = serve(move |_, _| unavailable()).await;
+        let url = object_config.generate_final_host("bucket", "key");
+        let error = client
+            .send_file(
+                &url,
+                Method::PUT,
+                headers.clone(),
+                ByteStream::from_reader(Cursor::new(b"data".to_vec())).with_len(4),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, UFileError::ApiError { status: 503, .. }));
+        assert!(error.is_throttled());
+        assert_eq!(log.lock().unwrap().len(), 1);
+    });
 }
@@ -1,9 +1,14 @@
 /// Default multipart size (4MB)
 pub(crate) const MULTIPART_SIZE: u32 = 4 << 20;
 
-#[allow(dead_code)]
 /// 默认buffer大小（512KB）
 pub(crate) const DEFAULT_BUFFER_SIZE: usize = 512 << 10;
 
 /// 默认并发数
 pub(crate) const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Max file size of a single put file request (512MB)
+pub(crate) const MAX_PUT_FILE_SIZE: u64 = 512 << 20;
+
+/// Default size from which uploads use multipart upload (64MB)
+pub(crate) const DEFAULT_MULTIPART_THRESHOLD: u64 = 64 << 20;
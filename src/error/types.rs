@@ -1,17 +1,221 @@
+use reqwest::{
+    Response, StatusCode,
+    header::{InvalidHeaderName, InvalidHeaderValue},
+};
 use thiserror::Error;
 
+use crate::api::BaseResponse;
+
 pub type UFileResult<T> = ::std::result::Result<T, UFileError>;
 
 #[derive(Debug, Error)]
 pub enum UFileError {
-    #[error("Api error")]
-    ApiError,
-    #[error("Network request error for: {0}")]
-    NetworkError(String),
-    #[error("Serialize error")]
-    SerializeError,
-    #[error("Deserialize error")]
-    DeserializeError,
-    #[error("Unknown error")]
-    UnknownError,
+    /// ucloud.cn answered the request with an error status.
+    #[error(
+        "Api error, status: {status}, ret code: {}, message: {message}{}",
+        ret_code.map(|c| c.to_string()).unwrap_or_else(|| "none".to_string()),
+        session_id.as_ref().map(|id| format!(", session id: {id}")).unwrap_or_default()
+    )]
+    ApiError {
+        /// HTTP status code of the response.
+        status: u16,
+        /// `RetCode` of the response body, `None` if the body is empty, e.g. of HEAD requests.
+        ret_code: Option<i32>,
+        /// `ErrMsg` of the response body, or the reason of the status if it is missing.
+        message: String,
+        /// `X-SessionId` of the response, to be given to ucloud.cn support.
+        session_id: Option<String>,
+    },
+    #[error("Network request error: {0}")]
+    NetworkError(#[source] reqwest::Error),
+    /// A response of ucloud.cn lacks a header or contains an invalid value.
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    /// The request is invalid, e.g. a required field is unset or a header value is not valid.
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    /// Reading or writing a local file failed.
+    #[error("Io error: {0}")]
+    IoError(#[from] std::io::Error),
+    /// The body of a response ended before all the expected bytes were received.
+    #[error("Incomplete body, expect {expected} bytes, received {received} bytes")]
+    IncompleteBody { expected: u64, received: u64 },
+    #[error("Object already exists: {0}")]
+    ObjectAlreadyExists(String),
+    #[error(
+        "Integrity check of {key}{} failed, expected: {expected}, actual: {actual}",
+        part_number.map(|n| format!(" part {n}")).unwrap_or_default()
+    )]
+    IntegrityMismatch {
+        key: String,
+        part_number: Option<usize>,
+        expected: String,
+        actual: String,
+    },
+    /// Any other failure.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl UFileError {
+    /// Build an [`UFileError::ApiError`] from an unsuccessful response, reading its body.
+    pub(crate) async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let session_id = response
+            .headers()
+            .get("x-sessionid")
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+        let body = response.bytes().await.unwrap_or_default();
+        let body = serde_json::from_slice::<BaseResponse>(&body).ok();
+        let reason = || status.canonical_reason().unwrap_or("Unknown").to_string();
+        UFileError::ApiError {
+            status: status.as_u16(),
+            ret_code: body.as_ref().map(|body| body.ret_code),
+            message: body
+                .and_then(|body| body.message)
+                .filter(|message| !message.is_empty())
+                .unwrap_or_else(reason),
+            session_id,
+        }
+    }
+
+    /// HTTP status code of an [`UFileError::ApiError`], or of a network error with a response.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            UFileError::ApiError { status, .. } => StatusCode::from_u16(*status).ok(),
+            UFileError::NetworkError(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    /// `RetCode` of an [`UFileError::ApiError`].
+    pub fn ret_code(&self) -> Option<i32> {
+        match self {
+            UFileError::ApiError { ret_code, .. } => *ret_code,
+            _ => None,
+        }
+    }
+
+    /// `ErrMsg` of an [`UFileError::ApiError`].
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            UFileError::ApiError { message, .. } => Some(message),
+            _ => None,
+        }
+    }
+
+    /// `X-SessionId` of an [`UFileError::ApiError`].
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            UFileError::ApiError { session_id, .. } => session_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the bucket or the object does not exist.
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    /// Whether the credentials are invalid or not allowed to access the bucket.
+    pub fn is_access_denied(&self) -> bool {
+        matches!(
+            self.status(),
+            Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+        )
+    }
+
+    /// Whether ucloud.cn throttled the request.
+    pub fn is_throttled(&self) -> bool {
+        matches!(
+            self.status(),
+            Some(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE)
+        )
+    }
+
+    /// Whether the failure is transient, so that the request may succeed if it is sent again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            UFileError::ApiError { status, .. } => {
+                matches!(status, 408 | 429) || (500..600).contains(status)
+            }
+            UFileError::NetworkError(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            UFileError::IncompleteBody { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<anyhow::Error> for UFileError {
+    /// Recover the [`UFileError`], the network or the io error wrapped by the error.
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<UFileError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<reqwest::Error>() {
+            Ok(error) => return UFileError::NetworkError(error),
+            Err(error) => error,
+        };
+        match error.downcast::<std::io::Error>() {
+            Ok(error) => UFileError::IoError(error),
+            Err(error) => UFileError::Other(error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for UFileError {
+    fn from(error: reqwest::Error) -> Self {
+        UFileError::NetworkError(error)
+    }
+}
+
+impl From<derive_builder::UninitializedFieldError> for UFileError {
+    /// A required field of a request builder is unset.
+    fn from(error: derive_builder::UninitializedFieldError) -> Self {
+        UFileError::InvalidRequest(error.to_string())
+    }
+}
+
+impl From<InvalidHeaderValue> for UFileError {
+    fn from(error: InvalidHeaderValue) -> Self {
+        UFileError::InvalidRequest(format!("invalid header value: {error}"))
+    }
+}
+
+impl From<InvalidHeaderName> for UFileError {
+    fn from(error: InvalidHeaderName) -> Self {
+        UFileError::InvalidRequest(format!("invalid header name: {error}"))
+    }
+}
+
+#[test]
+fn test_ufile_error_classification() {
+    let error = UFileError::ApiError {
+        status: 404,
+        ret_code: Some(-30010),
+        message: "object not exist".to_string(),
+        session_id: Some("abc".to_string()),
+    };
+    assert!(error.is_not_found());
+    assert!(!error.is_retryable());
+    assert_eq!(error.session_id(), Some("abc"));
+    assert_eq!(
+        error.to_string(),
+        "Api error, status: 404, ret code: -30010, message: object not exist, session id: abc"
+    );
+
+    let error = UFileError::from(anyhow::Error::from(UFileError::ApiError {
+        status: 503,
+        ret_code: None,
+        message: "Service Unavailable".to_string(),
+        session_id: None,
+    }));
+    assert!(error.is_throttled());
+    assert!(error.is_retryable());
+    assert!(matches!(
+        UFileError::from(anyhow::anyhow!("invalid")),
+        UFileError::Other(_)
+    ));
 }
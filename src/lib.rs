@@ -4,6 +4,7 @@ pub mod client;
 pub(crate) mod constant;
 pub mod error;
 mod macros;
+pub mod retry;
 pub mod util;
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
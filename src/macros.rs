@@ -4,6 +4,7 @@ macro_rules! define_operation_struct {
         use ::derive_builder::Builder;
 
         #[derive(Builder)]
+        #[builder(build_fn(error = "crate::error::UFileError"))]
         pub struct $name {
             object_config: $crate::api::ObjectConfig,
             // auth_service: $crate::AuthorizationService,
@@ -20,6 +21,7 @@ macro_rules! define_api_request {
         $request_name: ident,
         $operation_name: ty,
         $response_type: ty,
+        $($validate: ident,)?
         {
             $(
                 $(#[$attr:meta])*
@@ -29,7 +31,10 @@ macro_rules! define_api_request {
     ) => {
         /// Request configuration
         #[derive(::derive_builder::Builder)]
-        #[builder(pattern = "owned")]
+        #[builder(
+            pattern = "owned",
+            build_fn(error = "crate::error::UFileError" $(, $validate = "Self::validate")?)
+        )]
         pub struct $request_name {
             $(
                 $(#[$attr])*
@@ -51,19 +56,22 @@ macro_rules! define_api_request {
         #[async_trait::async_trait]
         impl $crate::api::ApiRequest for $request_name {
             type Response = $response_type;
-            type Error = ::anyhow::Error;
+            type Error = $crate::error::UFileError;
 
             async fn request(mut self) -> Result<Self::Response, Self::Error> {
                 use $crate::api::ApiOperation;
-                let object_config = self.object_config.take().unwrap();
-                let client = self.client.take().unwrap();
+                let object_config = self.object_config.take().ok_or_else(|| {
+                    $crate::error::UFileError::InvalidRequest("object_config is required".into())
+                })?;
+                let client = self.client.take().ok_or_else(|| {
+                    $crate::error::UFileError::InvalidRequest("client is required".into())
+                })?;
                 let operation = <$operation_name>::default()
                     .object_config(object_config)
                     .client(client)
                     .build()
-                    .map_err(|e| {
+                    .inspect_err(|e| {
                         ::tracing::error!("Failed to build operation, err: {:?}", e);
-                        anyhow::Error::from(e)
                     })?;
 
                 operation.execute(self).await
@@ -0,0 +1,244 @@
+//! This module contains the retry policy of the requests sent to ucloud.cn.
+//!
+//! Requests failing with a transient network error, a retryable status code or a retryable
+//! `RetCode` are sent again after an exponential backoff with jitter. Every attempt is built
+//! again, so that it is signed with a fresh `Date` and its body is replayed.
+
+use std::{
+    collections::{BTreeMap, hash_map::RandomState},
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    pin::Pin,
+    sync::{
+        Arc, Condvar, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How a request may be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryMode {
+    /// The request can be sent again safely, it is retried on any transient failure.
+    Idempotent,
+    /// Sending the request again may fail or have a different effect, e.g. finishing a multipart
+    /// upload. It is only retried if the connection could not be established, so that the
+    /// request never reached ucloud.cn.
+    ConnectOnly,
+    /// The body can not be replayed, the request is sent once.
+    Never,
+}
+
+/// Retry policy of a [`HttpClient`](crate::client::HttpClient).
+///
+/// The backoff before the n-th retry is `initial_backoff * 2^(n-1)` capped at `max_backoff`.
+/// With jitter a random duration between half and all of the backoff is waited.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Max attempts of a request including the first one, 1 disables retries.
+    /// Default: 3
+    pub max_attempts: u32,
+    /// Backoff before the first retry.
+    /// Default: 200ms
+    pub initial_backoff: Duration,
+    /// Max backoff between two attempts.
+    /// Default: 5s
+    pub max_backoff: Duration,
+    /// Whether to randomize the backoff.
+    /// Default: true
+    pub jitter: bool,
+    /// Status codes of responses which are retried.
+    /// Default: 408, 429, 500, 502, 503, 504
+    pub retryable_statuses: Vec<u16>,
+    /// `RetCode` of error responses which are retried whatever their status code is.
+    /// Default: none
+    pub retryable_ret_codes: Vec<i32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+            retryable_statuses: vec![408, 429, 500, 502, 503, 504],
+            retryable_ret_codes: vec![],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy sending every request once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Whether a request failing with `error` is retried.
+    pub(crate) fn is_retryable_error(&self, error: &reqwest::Error, mode: RetryMode) -> bool {
+        match mode {
+            RetryMode::Idempotent => error.is_connect() || error.is_timeout() || error.is_request(),
+            RetryMode::ConnectOnly => error.is_connect(),
+            RetryMode::Never => false,
+        }
+    }
+
+    /// Whether a request answered with `status` is retried.
+    pub(crate) fn is_retryable_status(&self, status: u16, mode: RetryMode) -> bool {
+        mode == RetryMode::Idempotent && self.retryable_statuses.contains(&status)
+    }
+
+    /// Whether the `RetCode` of error responses has to be read to decide if they are retried.
+    pub(crate) fn checks_ret_code(&self, mode: RetryMode) -> bool {
+        mode == RetryMode::Idempotent && !self.retryable_ret_codes.is_empty()
+    }
+
+    /// Whether a request answered with `ret_code` is retried.
+    pub(crate) fn is_retryable_ret_code(&self, ret_code: i32, mode: RetryMode) -> bool {
+        mode == RetryMode::Idempotent && self.retryable_ret_codes.contains(&ret_code)
+    }
+
+    /// Backoff before the retry following the failed `attempt`, starting from 1.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff);
+        if self.jitter {
+            let random = RandomState::new().build_hasher().finish();
+            backoff / 2 + backoff.mul_f64((random % 1000) as f64 / 2000.0)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Wait for `duration` without depending on an async runtime, a single timer thread wakes all
+/// sleeps at their deadline.
+pub(crate) fn sleep(duration: Duration) -> Sleep {
+    let state = Arc::new(Mutex::new((false, None::<Waker>)));
+    timer().schedule(Instant::now() + duration, state.clone());
+    Sleep { state }
+}
+
+/// Whether the sleep elapsed, and the waker of the task waiting for it.
+type SleepState = Arc<Mutex<(bool, Option<Waker>)>>;
+
+/// Timer thread shared by all sleeps.
+#[derive(Default)]
+struct Timer {
+    /// Pending sleeps by deadline, the sequence number keeps equal deadlines apart.
+    pending: Mutex<BTreeMap<(Instant, u64), SleepState>>,
+    next_id: AtomicU64,
+    scheduled: Condvar,
+}
+
+/// The timer, its thread is started by the first sleep.
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+    TIMER.get_or_init(|| {
+        thread::Builder::new()
+            .name("ufile-retry-timer".to_string())
+            .spawn(|| timer().run())
+            .expect("failed to spawn the retry timer thread");
+        Timer::default()
+    })
+}
+
+impl Timer {
+    fn schedule(&self, deadline: Instant, state: SleepState) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.pending.lock().unwrap().insert((deadline, id), state);
+        self.scheduled.notify_one();
+    }
+
+    /// Wake the sleeps whose deadline passed, then wait for the next deadline or a new sleep.
+    fn run(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            while let Some(entry) = pending.first_entry()
+                && entry.key().0 <= now
+            {
+                let state = entry.remove();
+                let mut state = state.lock().unwrap();
+                state.0 = true;
+                if let Some(waker) = state.1.take() {
+                    waker.wake();
+                }
+            }
+            pending = match pending.first_key_value() {
+                Some(((deadline, _), _)) => {
+                    let timeout = *deadline - now;
+                    self.scheduled.wait_timeout(pending, timeout).unwrap().0
+                }
+                None => self.scheduled.wait(pending).unwrap(),
+            };
+        }
+    }
+}
+
+/// Future returned by [`sleep`].
+pub(crate) struct Sleep {
+    state: SleepState,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.0 {
+            Poll::Ready(())
+        } else {
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn test_retry_policy_backoff() {
+    let policy = RetryPolicy {
+        jitter: false,
+        ..RetryPolicy::default()
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(800));
+    assert_eq!(policy.backoff(10), Duration::from_secs(5));
+
+    let policy = RetryPolicy::default();
+    for attempt in 1..5 {
+        let backoff = policy.backoff(attempt);
+        assert!(backoff >= Duration::from_millis(100 << (attempt - 1)));
+        assert!(backoff <= Duration::from_millis(200 << (attempt - 1)));
+    }
+    assert!(policy.is_retryable_status(503, RetryMode::Idempotent));
+    assert!(!policy.is_retryable_status(503, RetryMode::ConnectOnly));
+    assert!(!policy.is_retryable_status(404, RetryMode::Idempotent));
+}
+
+#[test]
+fn test_sleep() {
+    use futures_util::future::join_all;
+
+    // Sleeps scheduled out of order are all woken by the timer at their deadline.
+    crate::util::test_server::block_on(async {
+        let start = Instant::now();
+        let durations = [30, 10, 20, 10].map(Duration::from_millis);
+        let elapsed = join_all(durations.map(|duration| async move {
+            sleep(duration).await;
+            start.elapsed()
+        }))
+        .await;
+        for (duration, elapsed) in durations.iter().zip(elapsed) {
+            assert!(elapsed >= *duration);
+            assert!(elapsed < Duration::from_secs(1));
+        }
+    });
+}
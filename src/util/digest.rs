@@ -1,3 +1,9 @@
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read},
+    path::Path,
+};
+
 use sha1::{Digest, Sha1};
 
 /// A method used to calc hash value of source with sha1 digest alg.
@@ -6,3 +12,24 @@ pub fn sha1(source: impl AsRef<[u8]>) -> Vec<u8> {
     hasher.update(source);
     hasher.finalize().to_vec()
 }
+
+/// A method used to calc the `Content-MD5` header value of source, hex encoded md5 digest.
+pub fn content_md5(source: impl AsRef<[u8]>) -> String {
+    format!("{:x}", md5::compute(source))
+}
+
+/// A method used to calc the `Content-MD5` header value of a file, read in blocks of 64KB so
+/// that the file is never held in memory.
+pub fn file_content_md5(path: impl AsRef<Path>) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0; 64 << 10];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => return Ok(format!("{:x}", context.finalize())),
+            Ok(n) => context.consume(&buffer[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
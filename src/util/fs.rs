@@ -1,30 +1,37 @@
 //! This module contains some file system utils. But only support unix like system.
 
-use std::{fs::File, os::unix::fs::FileExt};
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+};
 
-use anyhow::Error;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use derive_builder::Builder;
 
+use crate::error::UFileError;
+
 /// The file chunk struct.
 /// It used to split file into pieces to upload.
 ///
 /// # Example
 ///
 /// ```
-/// use ucloud_rcore::util::fs::ChunkFile;
+/// use ufile_rus3::util::fs::ChunkFileBuilder;
 /// use bytes::Bytes;
 ///
-/// let chunk = ChunkFile::new()
+/// let chunk = ChunkFileBuilder::default()
 ///     .bytes(Bytes::from("hello world"))
 ///     .offset(0)
 ///     .size(11)
-///     .build();
-/// assert_eq!(chunk.bytes, Bytes::from("hello world"));
-/// assert_eq!(chunk.offset, 0);
-/// assert_eq!(chunk.size, 11);
+///     .build()
+///     .unwrap();
+/// assert_eq!(chunk.get_byte(), b"hello world");
+/// assert_eq!(chunk.chunk_size(), 11);
 /// ```
 #[derive(Builder)]
+#[builder(build_fn(error = "UFileError"))]
 pub struct ChunkFile {
     /// The file chunk bytes.
     bytes: Bytes,
@@ -68,34 +75,71 @@ impl ChunkFile {
     ///
     /// * `Ok(ChunkFile)` - The file chunk.
     /// * `Err(Error)` - The error.
-    #[cfg(any(target_os = "macos", target_os = "linux"))]
-    pub fn create_chunk_file(file: &File, offset: u64, size: u64) -> Result<ChunkFile, Error> {
-        let mut buffer = vec![0u8; size as usize];
-        let bytes = file.read_at(&mut buffer, offset);
-        match bytes {
-            Ok(n) => Ok(ChunkFileBuilder::default()
-                .bytes(Bytes::from(buffer[..n].to_vec()))
-                .offset(offset)
-                .size(size)
-                .build()
-                .map_err(|e| {
+    pub fn create_chunk_file(file: &File, offset: u64, size: u64) -> Result<ChunkFile, UFileError> {
+        Self::create_chunk_file_with_buffer(file, offset, size, BytesMut::new())
+    }
+
+    /// Create a new file chunk reading into the given buffer, so that buffers can be reused.
+    /// The bytes of the chunk can be turned back into a buffer with [`Bytes::try_into_mut`]
+    /// once they are no longer shared.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file to read.
+    /// * `offset` - The file chunk offset.
+    /// * `size` - The file chunk size. Less bytes are read if the end of file is reached.
+    /// * `buffer` - The buffer to read into, it is resized to `size`.
+    pub fn create_chunk_file_with_buffer(
+        file: &File,
+        offset: u64,
+        size: u64,
+        mut buffer: BytesMut,
+    ) -> Result<ChunkFile, UFileError> {
+        buffer.clear();
+        buffer.resize(size as usize, 0);
+        // `pread` might return less bytes than requested, read until the chunk is full or EOF.
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match file.read_at(&mut buffer[filled..], offset + filled as u64) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
                     tracing::error!(
-                        "Failed to create chunk file, offset: {}, size: {}, err: {:?}",
+                        "Failed to read file chunk, offset: {}, size: {}, err: {:?}",
                         offset,
                         size,
                         e
                     );
-                    anyhow::Error::from(e)
-                })?),
-            Err(e) => {
+                    return Err(e.into());
+                }
+            }
+        }
+        buffer.truncate(filled);
+        ChunkFileBuilder::default()
+            .bytes(buffer.freeze())
+            .offset(offset)
+            .size(size)
+            .build()
+            .map_err(|e| {
                 tracing::error!(
-                    "Failed to read file chunk, offset: {}, size: {}, err: {:?}",
+                    "Failed to create chunk file, offset: {}, size: {}, err: {:?}",
                     offset,
                     size,
                     e
                 );
-                Err(e.into())
-            }
-        }
+                e
+            })
     }
 }
+
+/// Replace the file at `path` atomically. The bytes are written to `<path>.tmp` and synced to
+/// disk before it is renamed to `path`, so that a crash leaves either the old or the new file.
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
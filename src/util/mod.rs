@@ -3,97 +3,231 @@
 pub mod byte;
 pub mod digest;
 pub mod fs;
+pub mod pool;
+#[cfg(test)]
+pub(crate) mod test_server;
 
 use std::{
     fmt::{Display, Formatter},
     fs::File,
     io::Read,
+    ops::RangeInclusive,
     path::Path,
 };
 
-use anyhow::anyhow;
 use base64::{Engine, engine::general_purpose};
 use sha1::{Digest, Sha1};
 
 use byteorder::{LittleEndian, WriteBytesExt};
 use serde::{Deserialize, Serialize};
 
+use crate::error::UFileError;
+
 /// Sha1 Length
 pub(crate) const SHA1_DIGEST_LENGTH: usize = 20;
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-pub(crate) struct ETag {
+/// UFile ETag of a file.
+///
+/// The file is split into blocks of 4MB, the ETag is the url safe base64 of the little endian
+/// block count followed by the SHA1 of the concatenated block SHA1s, or by the SHA1 of the data
+/// if there is only one block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ETag {
     #[serde(rename = "ETag")]
     pub etag: String,
+    /// Url safe base64 SHA1 of every block, empty if there is only one block.
     #[serde(rename = "PartEtags")]
     pub part_etags: Vec<String>,
 }
 
 impl ETag {
-    /// Create a new instance of ETag.
-    fn new() -> Self {
+    /// Compute the ETag of the file with blocks of `part_size` bytes.
+    pub fn from_file(file_path: impl AsRef<Path>, part_size: u32) -> Result<Self, UFileError> {
+        if part_size == 0 {
+            return Err(UFileError::InvalidRequest("part size is 0".to_string()));
+        }
+        let mut file = File::open(file_path)?;
+        let mut hasher = ETagHasher::with_block_size(part_size as usize);
+        let mut cache = vec![0; part_size as usize];
+        loop {
+            let bytes_read = file.read(&mut cache)?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&cache[0..bytes_read]);
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Compute the ETag from the SHA1 of every block in order.
+    pub fn from_block_hashes(block_hashes: &[[u8; SHA1_DIGEST_LENGTH]]) -> Self {
+        let mut buff = Vec::with_capacity(4 + SHA1_DIGEST_LENGTH);
+        buff.write_u32::<LittleEndian>(block_hashes.len() as u32)
+            .expect("Write block count to vec");
+        let mut part_etags = vec![];
+        match block_hashes {
+            [] => buff.resize(4 + SHA1_DIGEST_LENGTH, 0),
+            [block_hash] => buff.extend_from_slice(block_hash),
+            _ => {
+                buff.extend_from_slice(&Sha1::digest(block_hashes.concat()));
+                part_etags = block_hashes
+                    .iter()
+                    .map(|block_hash| general_purpose::URL_SAFE.encode(block_hash))
+                    .collect();
+            }
+        }
         Self {
-            etag: String::new(),
-            part_etags: vec![],
+            etag: general_purpose::URL_SAFE.encode(&buff),
+            part_etags,
         }
     }
 
-    pub fn from_file(file_path: impl AsRef<Path>, part_size: u32) -> Result<Self, anyhow::Error> {
-        if part_size == 0 {
-            return Err(anyhow!("part size is 0"));
+    /// Decode the SHA1 of a single block from its etag, which ucloud.cn returns either as the
+    /// bare base64 SHA1 or as the ETag of a single block with the block count header.
+    pub fn block_hash(etag: &str) -> Option<[u8; SHA1_DIGEST_LENGTH]> {
+        let etag = etag.trim_matches(|c| c == '\"' || c == '\'');
+        let decoded = general_purpose::URL_SAFE
+            .decode(etag)
+            .or_else(|_| general_purpose::STANDARD.decode(etag))
+            .ok()?;
+        match decoded.len() {
+            SHA1_DIGEST_LENGTH => decoded.try_into().ok(),
+            len if len == 4 + SHA1_DIGEST_LENGTH && decoded[..4] == 1u32.to_le_bytes() => {
+                decoded[4..].try_into().ok()
+            }
+            _ => None,
         }
-        let mut file = File::open(file_path)?;
-        let size = file.metadata()?.len();
-        let block_count = ((size as f64) / (part_size as f64)).ceil() as u32;
+    }
 
-        // head from block size.
-        // 准备头部和缓冲区
-        let mut head = Vec::new();
-        head.write_u32::<LittleEndian>(block_count)?;
+    /// Compute the ETag of in memory bytes with blocks of 4MB.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut hasher = ETagHasher::new();
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+}
 
-        let mut buff = Vec::with_capacity(4 + SHA1_DIGEST_LENGTH);
-        buff.extend_from_slice(&head);
-        buff.resize(4 + SHA1_DIGEST_LENGTH, 0);
+/// Incremental hasher computing the UFile [`ETag`] of data fed in arbitrary slices,
+/// e.g. while it streams through an upload or download.
+#[derive(Clone)]
+pub struct ETagHasher {
+    block_size: usize,
+    /// SHA1 of the current block.
+    block: Sha1,
+    /// Bytes of the current block.
+    block_len: usize,
+    /// SHA1 of the concatenated block SHA1s.
+    digest: Sha1,
+    /// SHA1 of the first block, which is the ETag hash if there is only one block.
+    first_block_hash: Option<[u8; SHA1_DIGEST_LENGTH]>,
+    part_etags: Vec<String>,
+    len: u64,
+}
 
-        let mut cache = vec![0; part_size as usize];
-        let mut e_tag = Self::default();
-
-        if block_count > 1 {
-            let mut digest = Sha1::new();
-
-            for _ in 0..block_count {
-                let bytes_read = file.read(&mut cache)?;
-                if bytes_read == 0 {
-                    break;
-                }
-
-                // 计算当前块的SHA1
-                let mut block_hasher = Sha1::new();
-                block_hasher.update(&cache[0..bytes_read]);
-                let block_hash = block_hasher.finalize();
-
-                // 保存块哈希并更新主哈希
-                let block_hash_b64 = general_purpose::URL_SAFE.encode(block_hash);
-                e_tag.part_etags.push(block_hash_b64);
-                digest.update(block_hash);
+impl Default for ETagHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ETagHasher {
+    /// Create a hasher with blocks of 4MB used by ucloud.cn.
+    pub fn new() -> Self {
+        Self::with_block_size(crate::constant::MULTIPART_SIZE as usize)
+    }
+
+    /// Create a hasher with blocks of `block_size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is 0.
+    pub fn with_block_size(block_size: usize) -> Self {
+        assert!(block_size > 0, "block size is 0");
+        Self {
+            block_size,
+            block: Sha1::new(),
+            block_len: 0,
+            digest: Sha1::new(),
+            first_block_hash: None,
+            part_etags: vec![],
+            len: 0,
+        }
+    }
+
+    /// Number of bytes hashed so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether no bytes are hashed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Feed the next bytes of the data.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        while !data.is_empty() {
+            let n = (self.block_size - self.block_len).min(data.len());
+            self.block.update(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+            if self.block_len == self.block_size {
+                let block = std::mem::replace(&mut self.block, Sha1::new());
+                self.finish_block(block);
             }
+        }
+    }
 
-            let sha1_res = digest.finalize();
-            buff[4..4 + SHA1_DIGEST_LENGTH].copy_from_slice(&sha1_res);
-        } else {
-            // 单个块的情况
-            let bytes_read = file.read(&mut cache)?;
-            if bytes_read > 0 {
-                let mut digest = Sha1::new();
-                digest.update(&cache[0..bytes_read]);
-                let sha1_res = digest.finalize();
-                buff[4..4 + SHA1_DIGEST_LENGTH].copy_from_slice(&sha1_res);
+    fn finish_block(&mut self, block: Sha1) {
+        let block_hash: [u8; SHA1_DIGEST_LENGTH] = block.finalize().into();
+        self.first_block_hash.get_or_insert(block_hash);
+        self.part_etags
+            .push(general_purpose::URL_SAFE.encode(block_hash));
+        self.digest.update(block_hash);
+        self.block_len = 0;
+    }
+
+    /// Compute the ETag of the bytes fed so far, the hasher can keep being updated.
+    pub fn finalize(&self) -> ETag {
+        let mut hasher = self.clone();
+        if hasher.block_len > 0 {
+            let block = std::mem::replace(&mut hasher.block, Sha1::new());
+            hasher.finish_block(block);
+        }
+        let block_count = hasher.part_etags.len() as u32;
+        let mut buff = Vec::with_capacity(4 + SHA1_DIGEST_LENGTH);
+        buff.write_u32::<LittleEndian>(block_count)
+            .expect("Write block count to vec");
+        match hasher.first_block_hash {
+            Some(_) if block_count > 1 => buff.extend_from_slice(&hasher.digest.finalize()),
+            Some(block_hash) => {
+                buff.extend_from_slice(&block_hash);
+                hasher.part_etags.clear();
             }
+            None => buff.resize(4 + SHA1_DIGEST_LENGTH, 0),
+        }
+        ETag {
+            etag: general_purpose::URL_SAFE.encode(&buff),
+            part_etags: hasher.part_etags,
         }
+    }
+}
 
-        e_tag.etag = general_purpose::URL_SAFE.encode(&buff);
-        Ok(e_tag)
+/// Split `total` bytes into consecutive inclusive byte ranges of `part_size` bytes.
+///
+/// The last range might be smaller than `part_size`. Returns an empty list if `total` is zero.
+pub fn split_ranges(total: u64, part_size: u64) -> Result<Vec<RangeInclusive<u64>>, UFileError> {
+    if part_size == 0 {
+        return Err(UFileError::InvalidRequest("part size is 0".to_string()));
     }
+    Ok((0..total.div_ceil(part_size))
+        .map(|i| {
+            let start = i * part_size;
+            let end = (start + part_size).min(total) - 1;
+            start..=end
+        })
+        .collect())
 }
 
 impl Display for ETag {
@@ -117,8 +251,83 @@ fn test_buff() {
 
 #[test]
 fn test_etag() {
-    let etag = ETag::from_file("HardOps_v988.zip", super::constant::MULTIPART_SIZE).unwrap();
-    let expected_etag = r#"{"PartEtags":["SHJn1NH0cVPKCvHqon457YNuW7A=","pkOIt9dDJitEoK8MPYmf7YdIako="],"ETag":"AgAAADOfVWTkq2axMvoOXgssP8tkSWxn"}"#;
-    println!("{}", expected_etag);
-    println!("{}", etag.to_string());
+    let part_size = super::constant::MULTIPART_SIZE;
+    let data = (0..part_size as usize + 10)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let dir = test_server::temp_dir("etag");
+    let path = dir.join("file");
+    std::fs::write(&path, &data).unwrap();
+    let etag = ETag::from_file(&path, part_size).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Two blocks: the block count followed by the SHA1 of the concatenated block SHA1s.
+    let blocks = data
+        .chunks(part_size as usize)
+        .map(|block| Sha1::digest(block).to_vec())
+        .collect::<Vec<_>>();
+    let mut expected = 2u32.to_le_bytes().to_vec();
+    expected.extend_from_slice(&Sha1::digest(blocks.concat()));
+    assert_eq!(etag.etag, general_purpose::URL_SAFE.encode(expected));
+    assert_eq!(
+        etag.part_etags,
+        blocks
+            .iter()
+            .map(|block| general_purpose::URL_SAFE.encode(block))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(etag, ETag::from_bytes(&data));
+}
+
+#[test]
+fn test_split_ranges() {
+    assert_eq!(split_ranges(10, 4).unwrap(), vec![0..=3, 4..=7, 8..=9]);
+    assert_eq!(split_ranges(8, 4).unwrap(), vec![0..=3, 4..=7]);
+    assert_eq!(split_ranges(1, 4).unwrap(), vec![0..=0]);
+    assert!(split_ranges(0, 4).unwrap().is_empty());
+    assert!(split_ranges(10, 0).is_err());
+}
+
+#[test]
+fn test_etag_hasher() {
+    let data = (0..10u8).collect::<Vec<_>>();
+    let sha1 = |data: &[u8]| -> [u8; SHA1_DIGEST_LENGTH] { Sha1::digest(data).into() };
+    let etag = |count: u32, hash: &[u8]| {
+        let mut buff = count.to_le_bytes().to_vec();
+        buff.extend_from_slice(hash);
+        general_purpose::URL_SAFE.encode(buff)
+    };
+
+    // Blocks of 4 bytes, fed in slices crossing the block boundaries.
+    let mut hasher = ETagHasher::with_block_size(4);
+    data.chunks(3).for_each(|chunk| hasher.update(chunk));
+    let blocks = data.chunks(4).map(sha1).collect::<Vec<_>>();
+    assert_eq!(hasher.finalize().etag, etag(3, &sha1(&blocks.concat())));
+    assert_eq!(hasher.finalize().part_etags.len(), 3);
+    assert_eq!(hasher.len(), 10);
+
+    // A single block hashes the data itself.
+    let mut hasher = ETagHasher::new();
+    hasher.update(&data);
+    assert_eq!(hasher.finalize().etag, etag(1, &sha1(&data)));
+    assert_eq!(ETag::from_bytes(&data), hasher.finalize());
+    assert_eq!(ETagHasher::new().finalize().etag, etag(0, &[0; 20]));
+}
+
+#[test]
+fn test_etag_block_hashes() {
+    let blocks = [[1u8; 20], [2u8; 20]];
+    let mut hasher = ETagHasher::with_block_size(1);
+    hasher.update(&[7, 8]);
+    let block_hashes = [Sha1::digest([7]).into(), Sha1::digest([8]).into()];
+    assert_eq!(ETag::from_block_hashes(&block_hashes), hasher.finalize());
+
+    let single = ETag::from_block_hashes(&blocks[..1]).etag;
+    assert_eq!(ETag::block_hash(&single), Some(blocks[0]));
+    let bare = general_purpose::URL_SAFE.encode(blocks[1]);
+    assert_eq!(ETag::block_hash(&format!("\"{bare}\"")), Some(blocks[1]));
+    assert_eq!(
+        ETag::block_hash(&ETag::from_block_hashes(&blocks).etag),
+        None
+    );
 }
@@ -2,12 +2,23 @@
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 
+/// Pool of reusable objects, clones share the same objects.
 pub struct ObjectPool<T> {
     objects: Arc<Mutex<VecDeque<T>>>,
-    creator: Box<dyn Fn() -> T + Send + Sync>,
+    creator: Arc<dyn Fn() -> T + Send + Sync>,
     max_size: usize,
 }
 
+impl<T> Clone for ObjectPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            objects: self.objects.clone(),
+            creator: self.creator.clone(),
+            max_size: self.max_size,
+        }
+    }
+}
+
 impl<T> ObjectPool<T> {
     pub fn new<F>(creator: F, max_size: usize) -> Self
     where
@@ -15,7 +26,7 @@ impl<T> ObjectPool<T> {
     {
         Self {
             objects: Arc::new(Mutex::new(VecDeque::new())),
-            creator: Box::new(creator),
+            creator: Arc::new(creator),
             max_size,
         }
     }
@@ -27,6 +38,7 @@ impl<T> ObjectPool<T> {
         PooledObject {
             object: Some(object),
             pool: self.objects.clone(),
+            max_size: self.max_size,
         }
     }
 }
@@ -34,6 +46,7 @@ impl<T> ObjectPool<T> {
 pub struct PooledObject<T> {
     object: Option<T>,
     pool: Arc<Mutex<VecDeque<T>>>,
+    max_size: usize,
 }
 
 impl<T> std::ops::Deref for PooledObject<T> {
@@ -54,7 +67,7 @@ impl<T> Drop for PooledObject<T> {
     fn drop(&mut self) {
         if let Some(object) = self.object.take() {
             let mut pool = self.pool.lock().unwrap();
-            if pool.len() < 10 {
+            if pool.len() < self.max_size {
                 // 限制池大小
                 pool.push_back(object);
             }
@@ -0,0 +1,193 @@
+//! A minimal HTTP/1.1 server on localhost answering the requests of tests, and other helpers
+//! of tests.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::api::{ObjectConfig, ObjectConfigBuilder};
+
+/// A request received by the test server.
+#[derive(Debug, Clone)]
+pub(crate) struct Received {
+    /// Address of the client, requests sent concurrently arrive on distinct connections.
+    pub peer: SocketAddr,
+    pub method: String,
+    pub uri: String,
+    /// Headers with lower cased names.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Response of the test server: status, extra headers and body.
+pub(crate) type Reply = (u16, Vec<(&'static str, String)>, String);
+
+/// Requests received so far, in order.
+pub(crate) type Log = Arc<Mutex<Vec<Received>>>;
+
+/// Start the server answering every request with `respond`, which is also given the index of
+/// the request. Returns an object config sending requests to the server and the request log.
+pub(crate) async fn serve<F>(respond: F) -> (ObjectConfig, Log)
+where
+    F: Fn(usize, &Received) -> Reply + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = format!("http://{}", listener.local_addr().unwrap());
+    let log: Log = Arc::default();
+    let respond = Arc::new(respond);
+    let server_log = log.clone();
+    tokio::spawn(async move {
+        while let Ok((socket, peer)) = listener.accept().await {
+            tokio::spawn(handle(socket, peer, respond.clone(), server_log.clone()));
+        }
+    });
+    let object_config = ObjectConfigBuilder::default()
+        .public_key("public")
+        .private_key("private")
+        .region("test")
+        .custom_host(host)
+        .build()
+        .unwrap();
+    (object_config, log)
+}
+
+async fn handle<F>(mut socket: TcpStream, peer: SocketAddr, respond: Arc<F>, log: Log)
+where
+    F: Fn(usize, &Received) -> Reply,
+{
+    let mut pending = Vec::new();
+    while let Some(received) = read_request(&mut socket, peer, &mut pending).await {
+        let head = received.method == "HEAD";
+        let (status, headers, body) = {
+            let mut log = log.lock().unwrap();
+            let reply = respond(log.len(), &received);
+            log.push(received);
+            reply
+        };
+        let mut response = format!(
+            "HTTP/1.1 {status} Test\r\nContent-Length: {}\r\n",
+            body.len()
+        );
+        for (name, value) in headers {
+            response.push_str(&format!("{name}: {value}\r\n"));
+        }
+        response.push_str("\r\n");
+        // Responses to HEAD carry the length of the body, but not the body.
+        if !head {
+            response.push_str(&body);
+        }
+        if socket.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Read from the socket until `pending` holds `len` bytes.
+async fn fill(socket: &mut TcpStream, pending: &mut Vec<u8>, len: usize) -> Option<()> {
+    let mut buffer = [0u8; 8192];
+    while pending.len() < len {
+        match socket.read(&mut buffer).await {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => pending.extend_from_slice(&buffer[..n]),
+        }
+    }
+    Some(())
+}
+
+/// Read until `pending` contains `delimiter`, returns its position.
+async fn find(socket: &mut TcpStream, pending: &mut Vec<u8>, delimiter: &[u8]) -> Option<usize> {
+    loop {
+        if let Some(position) = pending
+            .windows(delimiter.len())
+            .position(|w| w == delimiter)
+        {
+            return Some(position);
+        }
+        let len = pending.len() + 1;
+        fill(socket, pending, len).await?;
+    }
+}
+
+async fn read_request(
+    socket: &mut TcpStream,
+    peer: SocketAddr,
+    pending: &mut Vec<u8>,
+) -> Option<Received> {
+    let head_len = find(socket, pending, b"\r\n\r\n").await? + 4;
+    let head = String::from_utf8_lossy(&pending[..head_len]).to_string();
+    pending.drain(..head_len);
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let uri = request_line.next()?.to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect::<HashMap<_, _>>();
+
+    let mut body = Vec::new();
+    if headers
+        .get("transfer-encoding")
+        .is_some_and(|encoding| encoding.contains("chunked"))
+    {
+        loop {
+            let line_len = find(socket, pending, b"\r\n").await?;
+            let size =
+                usize::from_str_radix(String::from_utf8_lossy(&pending[..line_len]).trim(), 16)
+                    .ok()?;
+            fill(socket, pending, line_len + 2 + size + 2).await?;
+            body.extend_from_slice(&pending[line_len + 2..line_len + 2 + size]);
+            pending.drain(..line_len + 2 + size + 2);
+            if size == 0 {
+                break;
+            }
+        }
+    } else {
+        let len = headers
+            .get("content-length")
+            .and_then(|len| len.parse().ok())
+            .unwrap_or(0);
+        fill(socket, pending, len).await?;
+        body = pending.drain(..len).collect();
+    }
+    Some(Received {
+        peer,
+        method,
+        uri,
+        headers,
+        body,
+    })
+}
+
+/// Run the future of a test on a tokio runtime, the library itself does not depend on one.
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// Create an empty directory for the files of a test, unique across tests and test processes.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "ufile-rus3-{name}-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}